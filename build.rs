@@ -0,0 +1,192 @@
+// Generates the built in codebooks for `responder::exchange::codes` from the
+// word lists in `codebooks/words`, one word per line with `#` comments. The
+// build fails if any list holds a word that `responder::parser::parse` would
+// never read as a code, a reserved keyword, or words that could be mistaken
+// for one another.
+//
+// Set PARTYSKUNK_CONFUSABLES to have it report which words of each codebook
+// could be mistaken for one another, and which of them are left out for it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::Path,
+};
+
+#[path = "src/responder/exchange/analysis.rs"]
+mod analysis;
+
+const WORDS_DIR: &str = "codebooks/words";
+const HOMOPHONES_FILE: &str = "codebooks/homophones.txt";
+
+include!("src/responder/parser/words.rs");
+
+fn main() {
+    println!("cargo:rerun-if-changed=codebooks");
+    println!("cargo:rerun-if-changed=src/responder/parser/words.rs");
+    println!("cargo:rerun-if-changed=src/responder/exchange/analysis.rs");
+    println!("cargo:rerun-if-env-changed=PARTYSKUNK_CONFUSABLES");
+
+    let homophones = homophones();
+    let mut codebooks = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut paths: Vec<_> = fs::read_dir(WORDS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        if !is_word(&name.replace('_', "")) {
+            errors.push(format!("{}: name must be [a-z_]", path.display()));
+            continue;
+        }
+
+        let words = lines(&path);
+        for error in validate(&words, &homophones) {
+            errors.push(format!("{}: {}", path.display(), error));
+        }
+
+        codebooks.push((name, words));
+    }
+
+    if !errors.is_empty() {
+        panic!("invalid codebooks:\n{}", errors.join("\n"));
+    }
+
+    if env::var_os("PARTYSKUNK_CONFUSABLES").is_some() {
+        for (name, words) in &codebooks {
+            for line in report(name, words) {
+                println!("cargo:warning={}", line);
+            }
+        }
+    }
+
+    let mut generated = String::new();
+    for (name, words) in &codebooks {
+        let words: Vec<_> = words.iter().map(|w| format!("{:?}", w)).collect();
+        generated.push_str(&format!(
+            "pub const {}: &[&str] = &[{}];\n",
+            name.to_uppercase(),
+            words.join(", ")
+        ));
+    }
+
+    let entries: Vec<_> = codebooks
+        .iter()
+        .map(|(name, _)| format!("({:?}, {})", name, name.to_uppercase()))
+        .collect();
+    generated.push_str(&format!(
+        "pub const CODEBOOKS: &[(&str, &[&str])] = &[{}];\n",
+        entries.join(", ")
+    ));
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("codes.rs");
+    fs::write(out, generated).unwrap();
+}
+
+fn validate(
+    words: &[String],
+    homophones: &HashMap<String, HashSet<usize>>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    if words.is_empty() {
+        errors.push("no words".to_string());
+    }
+
+    let mut seen = HashSet::new();
+    for word in words {
+        if !is_word(word) {
+            errors.push(format!("{:?} must be [a-z]", word));
+        }
+        if RESERVED_WORDS.contains(&word.as_str()) {
+            errors.push(format!("{:?} is reserved", word));
+        }
+        if !seen.insert(word) {
+            errors.push(format!("{:?} is duplicated", word));
+        }
+    }
+
+    for (i, word) in words.iter().enumerate() {
+        for other in &words[i + 1..] {
+            if word == other {
+                continue;
+            }
+            if word.starts_with(other.as_str())
+                || other.starts_with(word.as_str())
+            {
+                errors.push(format!("{:?} and {:?} are prefixes", word, other));
+            }
+            if let (Some(a), Some(b)) =
+                (homophones.get(word), homophones.get(other))
+            {
+                if !a.is_disjoint(b) {
+                    errors.push(format!(
+                        "{:?} and {:?} are homophones",
+                        word, other
+                    ));
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+fn report(name: &str, words: &[String]) -> Vec<String> {
+    let conflicts = analysis::conflicts(words);
+    let mut excluded: Vec<_> =
+        analysis::exclusions(words).into_iter().collect();
+    excluded.sort();
+
+    let mut report = vec![format!(
+        "{}: {} words, {} confusable pairs, {} excluded",
+        name,
+        words.len(),
+        conflicts.len(),
+        excluded.len()
+    )];
+    for (a, b) in conflicts {
+        let distance = analysis::edit_distance(&a, &b);
+        let sounds = if analysis::metaphone(&a) == analysis::metaphone(&b) {
+            ", sound alike"
+        } else {
+            ""
+        };
+        report.push(format!("{} / {}: {} edits{}", a, b, distance, sounds));
+    }
+    report.push(format!("excluded: {}", excluded.join(", ")));
+    report
+}
+
+// Each word mapped to the groups it's in.
+fn homophones() -> HashMap<String, HashSet<usize>> {
+    let mut homophones: HashMap<_, HashSet<_>> = HashMap::new();
+    for (group, line) in lines(Path::new(HOMOPHONES_FILE)).iter().enumerate() {
+        for word in line.split_whitespace() {
+            homophones
+                .entry(word.to_string())
+                .or_default()
+                .insert(group);
+        }
+    }
+
+    homophones
+}
+
+fn lines(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_word(word: &str) -> bool {
+    !word.is_empty() && word.bytes().all(|byte| byte.is_ascii_lowercase())
+}
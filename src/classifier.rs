@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+use wasmbus_rpc::actor::prelude::RpcResult;
+
+mod permissive;
+pub use permissive::Permissive;
+
+mod stand_in;
+#[cfg(test)]
+pub use stand_in::StandIn;
+
+// Judges messages on what they say, past what a blocklist can catch. Nothing
+// is sent along with the message, so classifiers never learn who sent it.
+#[async_trait]
+pub trait Classifier {
+    // Tells whether the message should be refused.
+    async fn refuses(&mut self, message: &str) -> RpcResult<bool>;
+}
@@ -0,0 +1,20 @@
+use super::Classifier;
+use async_trait::async_trait;
+use wasmbus_rpc::actor::prelude::*;
+
+// Refuses nothing, leaving moderation to the configured lists alone.
+#[derive(Debug, Default)]
+pub struct Permissive {}
+
+impl Permissive {
+    pub fn new() -> Permissive {
+        Permissive {}
+    }
+}
+
+#[async_trait]
+impl Classifier for Permissive {
+    async fn refuses(&mut self, _message: &str) -> RpcResult<bool> {
+        Ok(false)
+    }
+}
@@ -0,0 +1,34 @@
+#![cfg(test)]
+
+use super::Classifier;
+use async_trait::async_trait;
+use wasmbus_rpc::actor::prelude::*;
+
+// Refuses exactly the messages it's given, and fails on any containing
+// "classifier error".
+#[derive(Debug, Default)]
+pub struct StandIn {
+    refused: Vec<String>,
+}
+
+impl StandIn {
+    pub fn new(refused: &[&str]) -> StandIn {
+        StandIn {
+            refused: refused
+                .iter()
+                .map(|message| message.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl Classifier for StandIn {
+    async fn refuses(&mut self, message: &str) -> RpcResult<bool> {
+        if message.contains("classifier error") {
+            return Err(RpcError::Other("unknown".to_string()));
+        }
+
+        Ok(self.refused.iter().any(|refused| refused == message))
+    }
+}
@@ -0,0 +1,170 @@
+use crate::http_client::{HttpClient, HttpRequest};
+use std::time::Duration;
+use wasmbus_rpc::actor::prelude::*;
+
+// Any server will do, since all of them give the time with every response.
+// This is the one that texts go out through anyway.
+#[cfg(target_arch = "wasm32")]
+const HOST_TIME_URL: &str = "https://api.twilio.com/";
+
+// Time since the unix epoch. Off of wasm32 this is anchored to `tokio`'s clock
+// so that tests can `pause` and `advance` it alongside `InMemory` expiry.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> Duration {
+    use std::cell::OnceCell;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tokio::time::Instant;
+
+    thread_local! {
+        static ANCHOR: OnceCell<(Instant, Duration)> = const { OnceCell::new() };
+    }
+
+    ANCHOR.with(|anchor| {
+        let (instant, since_epoch) = anchor.get_or_init(|| {
+            let since_epoch =
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            (Instant::now(), since_epoch)
+        });
+
+        *since_epoch + Instant::now().saturating_duration_since(*instant)
+    })
+}
+
+// wasm32-unknown-unknown has no clock at all, so the time is asked of the
+// host with `sync` by requests that need it, and stands for the rest of them.
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static NOW: std::cell::Cell<Option<Duration>> =
+        const { std::cell::Cell::new(None) };
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> Duration {
+    NOW.with(|now| now.get())
+        .expect("clock::sync is called before anything else")
+}
+
+// Should the host not give the time, the last that it gave stands in, and
+// only an instance that has never had the time fails.
+#[cfg(target_arch = "wasm32")]
+pub async fn sync<C: HttpClient>(client: &mut C) -> RpcResult<()> {
+    match host_time(HOST_TIME_URL, client).await {
+        Ok(now) => NOW.with(|cell| cell.set(Some(now))),
+        Err(error) if NOW.with(|now| now.get()).is_some() => {
+            crate::logger::log(format!("clock error: {:?}", error)).await;
+        }
+        Err(error) => return Err(error),
+    }
+
+    Ok(())
+}
+
+// Everything else has a clock of its own.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sync<C: HttpClient>(_client: &mut C) -> RpcResult<()> {
+    Ok(())
+}
+
+// The time as given by the `date` header of a response from `url`, to the
+// second.
+pub async fn host_time<C: HttpClient>(
+    url: &str,
+    client: &mut C,
+) -> RpcResult<Duration> {
+    let request = HttpRequest {
+        method: "HEAD".to_string(),
+        url: url.to_string(),
+        ..Default::default()
+    };
+    let response = client.request(&request).await?;
+    let date = response
+        .header
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("date"))
+        .and_then(|(_, values)| values.first())
+        .ok_or_else(|| RpcError::Other(format!("no date from {}", url)))?;
+
+    parse_http_date(date)
+        .ok_or_else(|| RpcError::Deser(format!("date: {}", date)))
+}
+
+// Only the one format that servers are meant to send, such as
+// "Sun, 06 Nov 1994 08:49:37 GMT".
+fn parse_http_date(date: &str) -> Option<Duration> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+        "Nov", "Dec",
+    ];
+
+    let parts: Vec<_> = date.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+    let mut time = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) =
+        (time.next()??, time.next()??, time.next()??);
+    if year < 1970
+        || !(1..=31).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 60
+    {
+        return None;
+    }
+
+    // Days since the epoch, counting years from March so that leap days fall
+    // at the end of them.
+    let (year, month) = if month > 2 {
+        (year, month - 3)
+    } else {
+        (year - 1, month + 9)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    let seconds = days * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http_client;
+
+    #[test]
+    fn it_parses_http_dates() {
+        let parse = parse_http_date;
+        let date = parse("Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(date, Some(Duration::from_secs(784_111_777)));
+        let date = parse("Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(date, Some(Duration::ZERO));
+        let date = parse("Thu, 29 Feb 2024 23:59:59 GMT");
+        assert_eq!(date, Some(Duration::from_secs(1_709_251_199)));
+
+        for date in [
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Sun, 06 Nov 1994 08:49:37 PST",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(parse(date), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_takes_the_time_from_responses() {
+        let stand_in = http_client::StandIn::start(200).await;
+        let mut client = http_client::Tcp::new();
+        let now = host_time(&stand_in.url, &mut client).await.unwrap();
+        assert_eq!(now, Duration::from_secs(784_111_777));
+        assert!(stand_in.requests()[0].starts_with("HEAD / HTTP/1.1"));
+    }
+}
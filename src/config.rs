@@ -0,0 +1,217 @@
+use crate::{key_value_store::KeyValueStore, responder::parser, twilio};
+use serde::Deserialize;
+use serde_json as json;
+use tokio::time::Duration;
+use wasmbus_rpc::actor::prelude::*;
+
+// Settings for a deployment, kept in the kv-store as JSON. Anything left out
+// takes its default, so a deployment without any config still runs, but it
+// can't create anything until it's given secrets.
+const CONFIG_KEY: &str = "partyskunk:config";
+// Secrets are kept apart from the rest of the config, in the secrets store.
+// These are a JSON list like `[{"id": "2", "secret": "..."}]`.
+const SECRETS_KEY: &str = "partyskunk:secrets";
+// Like `{"account_sid": "...", "auth_token": "...", "from": "+1..."}`.
+const TWILIO_KEY: &str = "partyskunk:twilio";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Either a built in codebook or one stored under
+    // `partyskunk:codebooks:<name>`.
+    pub codebook: String,
+    // How many words from the codebook make up a code.
+    pub code_words: u32,
+    // Whether to leave out codebook words that are easily mistaken for others.
+    pub exclude_confusable: bool,
+    // How long an expired code is held back before being handed out again,
+    // so that anyone still holding it can't read whatever comes next.
+    pub code_cooldown_secs: u64,
+    // For sending messages out, such as read receipts. Its auth token is a
+    // secret, so it's kept in the secrets store along with the others.
+    #[serde(skip)]
+    pub twilio: Option<twilio::Account>,
+    // What messages are sealed with, newest first. Messages are sealed with
+    // the newest and opened with whichever they were sealed with, so rotating
+    // is a matter of adding a new secret to the front. Codes only last a day,
+    // so the old one can be dropped a day later. Nothing can be created until
+    // there's at least one.
+    #[serde(skip)]
+    pub secrets: Vec<Secret>,
+    // Wording of its own for the texts that say the most about a deployment,
+    // so that each tenant can sound like itself.
+    pub templates: Templates,
+    // The single word that starts every prompt other than reading a code,
+    // and others that work just as well. Codebook words that clash with any
+    // of them are left out.
+    pub create_word: String,
+    pub create_aliases: Vec<String>,
+    // Numbers that are told more than others.
+    pub admins: Vec<String>,
+    // What messages can't say.
+    pub moderation: Moderation,
+    // How many SMS segments any reply can take up. Replies that would take
+    // more are cut short, so this is best left at no less than the 3 that a
+    // message can take.
+    pub reply_segments: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Moderation {
+    // Words and phrases, matched whole and whatever their case.
+    pub blocklist: Vec<String>,
+    // Domains that links can go to, subdomains included. Any can when there
+    // are none, other than those denied.
+    pub allowed_domains: Vec<String>,
+    pub denied_domains: Vec<String>,
+}
+
+impl Moderation {
+    pub fn allows_domain(&self, domain: &str) -> bool {
+        let matches = |entry: &String| {
+            let entry = entry.to_lowercase();
+            domain == entry || domain.ends_with(&format!(".{}", entry))
+        };
+        !self.denied_domains.iter().any(matches)
+            && (self.allowed_domains.is_empty()
+                || self.allowed_domains.iter().any(matches))
+    }
+}
+
+// In `tinytemplate` syntax. Any that are left out, or that don't render, are
+// worded the usual way.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Templates {
+    // Given {code}.
+    pub create_success: Option<String>,
+    // Everything there is to do, given the create {word}.
+    pub help: Option<String>,
+    // Texted on to creators, given {code} and {reply}.
+    pub relayed_reply: Option<String>,
+    // Given {code}, {read_at} and the masked {reader}.
+    pub receipt: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Secret {
+    // Stored alongside each sealed message, to find the secret again.
+    pub id: String,
+    pub secret: String,
+}
+
+// Kept out of anything logged.
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secret")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            codebook: "basic".to_string(),
+            // Pairs of words give tens of thousands of codes, where single
+            // words would only allow a couple hundred messages at a time.
+            code_words: 2,
+            exclude_confusable: true,
+            code_cooldown_secs: 3_600,
+            twilio: None,
+            secrets: Vec::new(),
+            templates: Templates::default(),
+            create_word: parser::DEFAULT_CREATE_PROMPT_WORD.to_string(),
+            create_aliases: Vec::new(),
+            admins: Vec::new(),
+            moderation: Moderation::default(),
+            reply_segments: 3,
+        }
+    }
+}
+
+impl Config {
+    pub async fn load<T: KeyValueStore>(store: &mut T) -> RpcResult<Config> {
+        let mut config: Config = match store.get(CONFIG_KEY).await? {
+            Some(config) => json::from_str(&config)
+                .map_err(|error| RpcError::Deser(error.to_string()))?,
+            None => Config::default(),
+        };
+
+        if let Some(secrets) = store.get_secret(SECRETS_KEY).await? {
+            config.secrets = json::from_str(&secrets)
+                .map_err(|error| RpcError::Deser(error.to_string()))?;
+        }
+        if let Some(twilio) = store.get_secret(TWILIO_KEY).await? {
+            let twilio = json::from_str(&twilio)
+                .map_err(|error| RpcError::Deser(error.to_string()))?;
+            config.twilio = Some(twilio);
+        }
+
+        Ok(config)
+    }
+
+    pub fn code_cooldown(&self) -> Duration {
+        Duration::from_secs(self.code_cooldown_secs)
+    }
+
+    // The create word along with its aliases.
+    pub fn create_words(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.create_word)
+            .chain(&self.create_aliases)
+            .map(String::as_str)
+    }
+
+    pub fn is_admin(&self, number: &str) -> bool {
+        self.admins.iter().any(|admin| admin == number)
+    }
+
+    pub fn is_create_word(&self, word: &str) -> bool {
+        self.create_words()
+            .any(|create_word| create_word.eq_ignore_ascii_case(word))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_value_store;
+
+    #[tokio::test]
+    async fn it_loads_secrets_from_the_secrets_store() {
+        let mut store = key_value_store::InMemory::new();
+        let config = Config::load(&mut store).await.unwrap();
+        assert!(config.secrets.is_empty());
+
+        // Not even when they're put in the config.
+        let secrets = r#"[{"id": "2", "secret": "second"}]"#;
+        let config = format!(r#"{{"code_words": 1, "secrets": {}}}"#, secrets);
+        let expires = Duration::ZERO;
+        store.set(CONFIG_KEY, &config, expires).await.unwrap();
+        let config = Config::load(&mut store).await.unwrap();
+        assert_eq!(config.code_words, 1);
+        assert!(config.secrets.is_empty());
+
+        store.set_secret(SECRETS_KEY, secrets);
+        let config = Config::load(&mut store).await.unwrap();
+        assert_eq!(config.secrets.len(), 1);
+        assert_eq!(config.secrets[0].id, "2");
+        assert!(!format!("{:?}", config).contains("second"));
+
+        store.set_secret(SECRETS_KEY, "not json");
+        assert!(Config::load(&mut store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn it_loads_the_twilio_account_from_the_secrets_store() {
+        let mut store = key_value_store::InMemory::new();
+        let config = Config::load(&mut store).await.unwrap();
+        assert!(config.twilio.is_none());
+
+        let twilio = r#"{"account_sid": "AC1", "auth_token": "token", "from": "+15550001111"}"#;
+        store.set_secret(TWILIO_KEY, twilio);
+        let config = Config::load(&mut store).await.unwrap();
+        assert_eq!(config.twilio.unwrap().account_sid, "AC1");
+    }
+}
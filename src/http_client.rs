@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wasmbus_rpc::actor::prelude::*;
+
+mod actor;
+#[cfg(target_arch = "wasm32")]
+pub use actor::Actor;
+
+mod tcp;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tcp::Tcp;
+
+mod stand_in;
+#[cfg(test)]
+pub use stand_in::StandIn;
+
+// Shaped like the httpclient interface's, which is what the actor sends.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, Vec<String>>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HttpResponse {
+    #[serde(rename = "statusCode")]
+    #[serde(default)]
+    pub status_code: u16,
+    #[serde(default)]
+    pub header: HashMap<String, Vec<String>>,
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+}
+
+#[async_trait]
+pub trait HttpClient {
+    async fn request(
+        &mut self,
+        request: &HttpRequest,
+    ) -> RpcResult<HttpResponse>;
+}
+
+// The operation on the httpclient provider that takes an `HttpRequest`.
+pub const REQUEST_METHOD: &str = "HttpClient.Request";
+
+// Messages to and from providers are msgpack, with fields by name.
+pub fn encode(request: &HttpRequest) -> RpcResult<Vec<u8>> {
+    wasmbus_rpc::common::serialize(request)
+}
+
+pub fn decode(response: &[u8]) -> RpcResult<HttpResponse> {
+    wasmbus_rpc::common::deserialize(response)
+        .map_err(|error| RpcError::Deser(format!("'{}': HttpResponse", error)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Shaped exactly like the httpclient interface's own, so that anything
+    // left out, added or renamed fails to decode.
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    struct ProviderRequest {
+        method: String,
+        url: String,
+        headers: HashMap<String, Vec<String>>,
+        #[serde(with = "serde_bytes")]
+        body: Vec<u8>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    struct ProviderResponse {
+        #[serde(rename = "statusCode")]
+        status_code: u16,
+        header: HashMap<String, Vec<String>>,
+        #[serde(with = "serde_bytes")]
+        body: Vec<u8>,
+    }
+
+    #[test]
+    fn it_encodes_requests_for_the_provider() {
+        let request = HttpRequest {
+            method: "POST".to_string(),
+            url: "https://example.com/path".to_string(),
+            headers: HashMap::from([(
+                "content-type".to_string(),
+                vec!["text/plain".to_string()],
+            )]),
+            body: b"body".to_vec(),
+        };
+
+        let encoded = encode(&request).unwrap();
+        let decoded: ProviderRequest =
+            wasmbus_rpc::common::deserialize(&encoded).unwrap();
+        assert_eq!(decoded.method, "POST");
+        assert_eq!(decoded.url, "https://example.com/path");
+        assert_eq!(decoded.headers["content-type"], vec!["text/plain"]);
+        assert_eq!(decoded.body, b"body");
+
+        // Bodies go as binary rather than as arrays of numbers.
+        let body = [0xc4, 4, b'b', b'o', b'd', b'y'];
+        assert!(encoded.windows(body.len()).any(|bytes| bytes == body));
+        assert_eq!(REQUEST_METHOD, "HttpClient.Request");
+    }
+
+    #[test]
+    fn it_decodes_responses_from_the_provider() {
+        let response = ProviderResponse {
+            status_code: 201,
+            header: HashMap::from([(
+                "date".to_string(),
+                vec!["Sun, 06 Nov 1994 08:49:37 GMT".to_string()],
+            )]),
+            body: b"{}".to_vec(),
+        };
+
+        let encoded = wasmbus_rpc::common::serialize(&response).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded.status_code, 201);
+        assert_eq!(decoded.header["date"], response.header["date"]);
+        assert_eq!(decoded.body, b"{}");
+    }
+}
@@ -0,0 +1,42 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::borrow::Cow;
+use wasmbus_rpc::{actor::prelude::*, common::Message};
+
+use super::{HttpClient, HttpRequest, HttpResponse, REQUEST_METHOD};
+
+// There's no interface crate to lean on, so this sends to the httpclient
+// provider the way that one would.
+pub struct Actor<'a> {
+    ctx: &'a Context,
+}
+
+impl Actor<'_> {
+    pub fn new(ctx: &Context) -> Actor<'_> {
+        Actor { ctx }
+    }
+}
+
+#[async_trait]
+impl HttpClient for Actor<'_> {
+    async fn request(
+        &mut self,
+        request: &HttpRequest,
+    ) -> RpcResult<HttpResponse> {
+        let transport =
+            WasmHost::to_provider("wasmcloud:httpclient", "default")?;
+        let buf = super::encode(request)?;
+        let response = transport
+            .send(
+                self.ctx,
+                Message {
+                    method: REQUEST_METHOD,
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+
+        super::decode(&response)
+    }
+}
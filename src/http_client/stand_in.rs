@@ -0,0 +1,73 @@
+#![cfg(test)]
+
+use std::sync::{Arc, Mutex};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+// Stands in for an API that the actor calls out to, answering every request
+// with `status` and keeping hold of what was asked. It always gives the same
+// date, the one from the HTTP spec.
+pub struct StandIn {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl StandIn {
+    pub async fn start(status: u16) -> StandIn {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                // Requests come from `Tcp`, which closes its side only after
+                // reading the response, so read up to the end of the body.
+                let mut buffer = [0; 1024];
+                while !is_complete(&request) {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let request = String::from_utf8_lossy(&request).to_string();
+                received.lock().unwrap().push(request);
+                let response = format!(
+                    "HTTP/1.1 {} Stand In\r\n\
+                     date: Sun, 06 Nov 1994 08:49:37 GMT\r\n\
+                     content-length: 2\r\n\r\n{{}}",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        StandIn { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn is_complete(request: &[u8]) -> bool {
+    let request = String::from_utf8_lossy(request);
+    let (head, body) = match request.split_once("\r\n\r\n") {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let length = head
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, length)| length.parse().ok())
+        .unwrap_or(0);
+    body.len() >= length
+}
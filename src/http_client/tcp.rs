@@ -0,0 +1,84 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use super::{HttpClient, HttpRequest, HttpResponse};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use wasmbus_rpc::actor::prelude::*;
+
+// Plain HTTP/1.1 over a socket, just enough to talk to local stand-ins for the
+// APIs that the actor calls out to.
+#[derive(Debug, Default)]
+pub struct Tcp {}
+
+impl Tcp {
+    #[allow(dead_code)] // Just to settle `cfg` confusion.
+    pub fn new() -> Tcp {
+        Tcp {}
+    }
+}
+
+#[async_trait]
+impl HttpClient for Tcp {
+    async fn request(
+        &mut self,
+        request: &HttpRequest,
+    ) -> RpcResult<HttpResponse> {
+        let io = |error: std::io::Error| RpcError::Other(error.to_string());
+        let url = request.url.strip_prefix("http://").ok_or_else(|| {
+            RpcError::Other(format!("not plain http: {}", request.url))
+        })?;
+        let (host, path) = match url.find('/') {
+            Some(i) => url.split_at(i),
+            None => (url, "/"),
+        };
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nhost: {}\r\nconnection: close\r\n\
+             content-length: {}\r\n",
+            request.method,
+            path,
+            host,
+            request.body.len()
+        );
+        for (name, values) in &request.headers {
+            for value in values {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        head.push_str("\r\n");
+
+        let mut stream = TcpStream::connect(host).await.map_err(io)?;
+        stream.write_all(head.as_bytes()).await.map_err(io)?;
+        stream.write_all(&request.body).await.map_err(io)?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.map_err(io)?;
+
+        let response = String::from_utf8_lossy(&response);
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+        let mut lines = head.lines();
+        let status_code = lines
+            .next()
+            .and_then(|status| status.split(' ').nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| RpcError::Deser(format!("response: {}", head)))?;
+
+        // Names are lowercased, as the httpclient provider gives them.
+        let mut header = HashMap::<_, Vec<_>>::new();
+        for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+            header
+                .entry(name.trim().to_lowercase())
+                .or_default()
+                .push(value.trim().to_string());
+        }
+
+        Ok(HttpResponse {
+            status_code,
+            header,
+            body: body.as_bytes().to_vec(),
+        })
+    }
+}
@@ -9,6 +9,9 @@ pub use actor::Actor;
 mod in_memory;
 pub use in_memory::InMemory;
 
+mod prefixed;
+pub use prefixed::Prefixed;
+
 #[async_trait]
 pub trait KeyValueStore {
     async fn get(&mut self, key: &str) -> RpcResult<Option<String>>;
@@ -18,5 +21,137 @@ pub trait KeyValueStore {
         value: &str,
         expires: Duration,
     ) -> RpcResult<()>;
+    // Sets `key` only if it isn't already, telling whether it was. Of callers
+    // racing to set the same key, only one ever succeeds.
+    async fn set_if_absent(
+        &mut self,
+        key: &str,
+        value: &str,
+        expires: Duration,
+    ) -> RpcResult<bool>;
     async fn incr_by(&mut self, key: &str, value: i32) -> RpcResult<i32>;
+    // Secrets aren't kept alongside everything else, but behind a store of
+    // their own, so that a dump of this one doesn't give them away.
+    async fn get_secret(&mut self, key: &str) -> RpcResult<Option<String>>;
+    // Removes `key`, telling whether it was there.
+    async fn del(&mut self, key: &str) -> RpcResult<bool>;
+
+    // Lists follow redis semantics: pushing appends, ranges are inclusive and
+    // negative indexes count back from the end.
+    async fn list_push(&mut self, list: &str, value: &str) -> RpcResult<u32>;
+    // Removes and returns the head of the list. Only one caller ever gets a
+    // given value, even when popping concurrently.
+    async fn list_pop(&mut self, list: &str) -> RpcResult<Option<String>>;
+    async fn list_range(
+        &mut self,
+        list: &str,
+        start: i32,
+        stop: i32,
+    ) -> RpcResult<Vec<String>>;
+    // Removes every occurrence of `value`, telling whether there were any.
+    async fn list_del(&mut self, list: &str, value: &str) -> RpcResult<bool>;
+}
+
+// For stores without a setnx of their own: callers race to be first to
+// increment a claim on the key, and the winner gives the claim the value's
+// expiry before setting it, so that the key can be claimed again once the
+// value is gone. Keys set without claiming them are only noticed by checking
+// first.
+pub async fn set_if_claimed<T: KeyValueStore>(
+    store: &mut T,
+    key: &str,
+    value: &str,
+    expires: Duration,
+) -> RpcResult<bool> {
+    if store.get(key).await?.is_some() {
+        return Ok(false);
+    }
+
+    let claim = claim_key(key);
+    if store.incr_by(&claim, 1).await? != 1 {
+        return Ok(false);
+    }
+
+    // Anyone incrementing in between has already lost, so this only sets the
+    // expiry.
+    store.set(&claim, "1", expires).await?;
+    store.set(key, value, expires).await?;
+    Ok(true)
+}
+
+pub fn claim_key(key: &str) -> String {
+    format!("{}:claim", key)
+}
+
+// Counts one more at `key`, which expires `expires` after the first count.
+// The count is laid down before it's incremented, rather than given its
+// expiry after, so that no interleaving of counts can set it back.
+pub async fn count<T: KeyValueStore>(
+    store: &mut T,
+    key: &str,
+    expires: Duration,
+) -> RpcResult<i32> {
+    store.set_if_absent(key, "0", expires).await?;
+    store.incr_by(key, 1).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_claims_keys_again_once_they_expire() {
+        tokio::time::pause();
+        let mut store = InMemory::new();
+        let expires = Duration::from_secs(60);
+        let set = set_if_claimed(&mut store, "key", "a", expires).await;
+        assert!(set.unwrap());
+
+        // Not even once the value is gone, while the claim is still there.
+        store.set("key", "a", Duration::from_secs(1)).await.unwrap();
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let set = set_if_claimed(&mut store, "key", "b", expires).await;
+        assert!(!set.unwrap());
+
+        tokio::time::advance(expires).await;
+        let set = set_if_claimed(&mut store, "key", "b", expires).await;
+        assert!(set.unwrap());
+        assert_eq!(store.get("key").await.unwrap().unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn it_claims_deleted_keys_again() {
+        let mut store = InMemory::new();
+        let expires = Duration::from_secs(60);
+        let set = set_if_claimed(&mut store, "key", "a", expires).await;
+        assert!(set.unwrap());
+
+        // Deleting the value alone leaves it claimed until the claim expires.
+        store.del("key").await.unwrap();
+        let set = set_if_claimed(&mut store, "key", "b", expires).await;
+        assert!(!set.unwrap());
+
+        store.del(&claim_key("key")).await.unwrap();
+        let set = set_if_claimed(&mut store, "key", "b", expires).await;
+        assert!(set.unwrap());
+        assert_eq!(store.get("key").await.unwrap().unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn it_counts_from_the_first_count() {
+        tokio::time::pause();
+        let mut store = InMemory::new();
+        let expires = Duration::from_secs(60);
+
+        // Another count lands after the first is laid down, but before it's
+        // incremented.
+        assert!(store.set_if_absent("key", "0", expires).await.unwrap());
+        assert_eq!(count(&mut store, "key", expires).await.unwrap(), 1);
+        assert_eq!(store.incr_by("key", 1).await.unwrap(), 2);
+
+        tokio::time::advance(expires / 2).await;
+        assert_eq!(count(&mut store, "key", expires).await.unwrap(), 3);
+        tokio::time::advance(expires / 2).await;
+        assert_eq!(count(&mut store, "key", expires).await.unwrap(), 1);
+    }
 }
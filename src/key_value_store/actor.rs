@@ -3,11 +3,15 @@
 use tokio::time::Duration;
 use wasmbus_rpc::actor::prelude::*;
 use wasmcloud_interface_keyvalue::{
-    IncrementRequest, KeyValue, KeyValueSender, SetRequest,
+    IncrementRequest, KeyValue, KeyValueSender, ListAddRequest, ListDelRequest,
+    ListRangeRequest, SetRequest,
 };
 
 use super::KeyValueStore;
 
+// The link to the store that secrets are kept in, such as a vault.
+const SECRETS_LINK: &str = "secrets";
+
 pub struct Actor<'a> {
     ctx: &'a Context,
 }
@@ -48,6 +52,16 @@ impl KeyValueStore for Actor<'_> {
             .await
     }
 
+    // The interface has no setnx.
+    async fn set_if_absent(
+        &mut self,
+        key: &str,
+        value: &str,
+        expires: Duration,
+    ) -> RpcResult<bool> {
+        super::set_if_claimed(self, key, value, expires).await
+    }
+
     async fn incr_by(&mut self, key: &str, value: i32) -> RpcResult<i32> {
         KeyValueSender::new()
             .increment(
@@ -59,4 +73,77 @@ impl KeyValueStore for Actor<'_> {
             )
             .await
     }
+
+    async fn get_secret(&mut self, key: &str) -> RpcResult<Option<String>> {
+        let response = KeyValueSender::new_with_link(SECRETS_LINK)?
+            .get(self.ctx, key)
+            .await?;
+        if response.exists {
+            Ok(Some(response.value))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn del(&mut self, key: &str) -> RpcResult<bool> {
+        KeyValueSender::new().del(self.ctx, key).await
+    }
+
+    async fn list_push(&mut self, list: &str, value: &str) -> RpcResult<u32> {
+        KeyValueSender::new()
+            .list_add(
+                self.ctx,
+                &ListAddRequest {
+                    list_name: list.to_string(),
+                    value: value.to_string(),
+                },
+            )
+            .await
+    }
+
+    // The interface has no pop, so peek at the head and race to delete it.
+    // Whoever deletes it is the one who popped it.
+    async fn list_pop(&mut self, list: &str) -> RpcResult<Option<String>> {
+        loop {
+            let head = self.list_range(list, 0, 0).await?;
+            let head = match head.into_iter().next() {
+                Some(head) => head,
+                None => return Ok(None),
+            };
+
+            if self.list_del(list, &head).await? {
+                return Ok(Some(head));
+            }
+        }
+    }
+
+    async fn list_range(
+        &mut self,
+        list: &str,
+        start: i32,
+        stop: i32,
+    ) -> RpcResult<Vec<String>> {
+        KeyValueSender::new()
+            .list_range(
+                self.ctx,
+                &ListRangeRequest {
+                    list_name: list.to_string(),
+                    start,
+                    stop,
+                },
+            )
+            .await
+    }
+
+    async fn list_del(&mut self, list: &str, value: &str) -> RpcResult<bool> {
+        KeyValueSender::new()
+            .list_del(
+                self.ctx,
+                &ListDelRequest {
+                    list_name: list.to_string(),
+                    value: value.to_string(),
+                },
+            )
+            .await
+    }
 }
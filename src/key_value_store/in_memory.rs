@@ -1,14 +1,17 @@
 use super::KeyValueStore;
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 // `Instant` won't work on wasm32.
 use tokio::time::{Duration, Instant};
 use wasmbus_rpc::actor::prelude::*;
 
+// Stands in for redis, so it should behave like it.
 #[derive(Debug)]
 pub struct InMemory {
     map: HashMap<String, String>,
     expiry: HashMap<String, Instant>,
+    lists: HashMap<String, VecDeque<String>>,
+    secrets: HashMap<String, String>,
 }
 
 impl InMemory {
@@ -17,6 +20,22 @@ impl InMemory {
         InMemory {
             map: HashMap::new(),
             expiry: HashMap::new(),
+            lists: HashMap::new(),
+            secrets: HashMap::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_secret(&mut self, key: &str, value: &str) {
+        self.secrets.insert(key.to_string(), value.to_string());
+    }
+
+    fn expire(&mut self, key: &str) {
+        if let Some(time) = self.expiry.get(key) {
+            if time <= &Instant::now() {
+                self.expiry.remove(key);
+                self.map.remove(key);
+            }
         }
     }
 }
@@ -24,43 +43,185 @@ impl InMemory {
 #[async_trait]
 impl KeyValueStore for InMemory {
     async fn get(&mut self, key: &str) -> RpcResult<Option<String>> {
-        let value = match self.expiry.get(key) {
-            None => None,
-            Some(time) => {
-                if time > &Instant::now() {
-                    self.map.get(key)
-                } else {
-                    self.expiry.remove(key);
-                    self.map.remove(key);
-                    None
-                }
-            }
-        };
-
-        Ok(value.cloned())
+        self.expire(key);
+        Ok(self.map.get(key).cloned())
     }
 
+    // Like the redis provider, no expiry at all is given as zero.
     async fn set(
         &mut self,
         key: &str,
         value: &str,
         expires: Duration,
     ) -> RpcResult<()> {
-        let expires = Instant::now() + expires;
-        self.expiry.insert(key.to_string(), expires);
+        if expires.is_zero() {
+            self.expiry.remove(key);
+        } else {
+            let expires = Instant::now() + expires;
+            self.expiry.insert(key.to_string(), expires);
+        }
         self.map.insert(key.to_string(), value.to_string());
 
         Ok(())
     }
 
+    async fn set_if_absent(
+        &mut self,
+        key: &str,
+        value: &str,
+        expires: Duration,
+    ) -> RpcResult<bool> {
+        if self.get(key).await?.is_some() {
+            return Ok(false);
+        }
+
+        self.set(key, value, expires).await?;
+        Ok(true)
+    }
+
+    // A missing key counts from zero and keeps any expiry it has.
     async fn incr_by(&mut self, key: &str, value: i32) -> RpcResult<i32> {
+        self.expire(key);
         let value = match self.map.get(key) {
             Some(i) => i.parse::<i32>().unwrap() + value,
-            None => 0,
+            None => value,
         };
 
         self.map.insert(key.to_string(), value.to_string());
 
         Ok(value)
     }
+
+    async fn get_secret(&mut self, key: &str) -> RpcResult<Option<String>> {
+        Ok(self.secrets.get(key).cloned())
+    }
+
+    async fn del(&mut self, key: &str) -> RpcResult<bool> {
+        self.expire(key);
+        self.expiry.remove(key);
+        Ok(self.map.remove(key).is_some())
+    }
+
+    async fn list_push(&mut self, list: &str, value: &str) -> RpcResult<u32> {
+        let list = self.lists.entry(list.to_string()).or_default();
+        list.push_back(value.to_string());
+        Ok(list.len() as u32)
+    }
+
+    async fn list_pop(&mut self, list: &str) -> RpcResult<Option<String>> {
+        let value = match self.lists.get_mut(list) {
+            Some(values) => values.pop_front(),
+            None => None,
+        };
+
+        Ok(value)
+    }
+
+    async fn list_range(
+        &mut self,
+        list: &str,
+        start: i32,
+        stop: i32,
+    ) -> RpcResult<Vec<String>> {
+        let values = match self.lists.get(list) {
+            Some(values) => values,
+            None => return Ok(Vec::new()),
+        };
+
+        let len = values.len() as i32;
+        let index = |i: i32| if i < 0 { (len + i).max(0) } else { i };
+        let (start, stop) = (index(start), index(stop).min(len - 1));
+        if start > stop {
+            return Ok(Vec::new());
+        }
+
+        Ok(values
+            .range(start as usize..=stop as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_del(&mut self, list: &str, value: &str) -> RpcResult<bool> {
+        let values = match self.lists.get_mut(list) {
+            Some(values) => values,
+            None => return Ok(false),
+        };
+
+        let len = values.len();
+        values.retain(|v| v != value);
+        Ok(values.len() < len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_counts_from_zero() {
+        let mut store = InMemory::new();
+        assert_eq!(store.incr_by("key", 1).await.unwrap(), 1);
+        assert_eq!(store.incr_by("key", 2).await.unwrap(), 3);
+        assert_eq!(store.get("key").await.unwrap().unwrap(), "3");
+    }
+
+    #[tokio::test]
+    async fn it_expires_values() {
+        tokio::time::pause();
+        let mut store = InMemory::new();
+        store
+            .set("key", "value", Duration::from_secs(1))
+            .await
+            .unwrap();
+        store.set("forever", "value", Duration::ZERO).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(store.get("key").await.unwrap(), None);
+        assert_eq!(store.incr_by("key", 1).await.unwrap(), 1);
+        assert!(store.get("forever").await.unwrap().is_some());
+
+        assert!(store.del("forever").await.unwrap());
+        assert!(!store.del("forever").await.unwrap());
+        assert_eq!(store.get("forever").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn it_sets_only_absent_values() {
+        tokio::time::pause();
+        let mut store = InMemory::new();
+        let expires = Duration::from_secs(1);
+        assert!(store.set_if_absent("key", "a", expires).await.unwrap());
+        assert!(!store.set_if_absent("key", "b", expires).await.unwrap());
+        assert_eq!(store.get("key").await.unwrap().unwrap(), "a");
+
+        tokio::time::advance(expires).await;
+        assert!(store.set_if_absent("key", "b", expires).await.unwrap());
+        assert_eq!(store.get("key").await.unwrap().unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn it_handles_lists() {
+        let mut store = InMemory::new();
+        assert_eq!(store.list_pop("list").await.unwrap(), None);
+        assert!(store.list_range("list", 0, -1).await.unwrap().is_empty());
+
+        for (i, value) in ["a", "b", "c", "b"].into_iter().enumerate() {
+            let len = store.list_push("list", value).await.unwrap();
+            assert_eq!(len as usize, i + 1);
+        }
+
+        let range = store.list_range("list", 0, -1).await.unwrap();
+        assert_eq!(range, vec!["a", "b", "c", "b"]);
+        let range = store.list_range("list", 1, 2).await.unwrap();
+        assert_eq!(range, vec!["b", "c"]);
+        let range = store.list_range("list", -2, 10).await.unwrap();
+        assert_eq!(range, vec!["c", "b"]);
+        assert!(store.list_range("list", 3, 1).await.unwrap().is_empty());
+
+        assert!(store.list_del("list", "b").await.unwrap());
+        assert!(!store.list_del("list", "b").await.unwrap());
+        assert_eq!(store.list_pop("list").await.unwrap().unwrap(), "a");
+        assert_eq!(store.list_pop("list").await.unwrap().unwrap(), "c");
+        assert_eq!(store.list_pop("list").await.unwrap(), None);
+    }
 }
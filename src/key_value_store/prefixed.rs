@@ -0,0 +1,126 @@
+use super::KeyValueStore;
+use async_trait::async_trait;
+use tokio::time::Duration;
+use wasmbus_rpc::actor::prelude::*;
+
+// Keeps everything under a prefix of another store, so that whoever uses it
+// can't reach anything outside of it. An empty prefix passes keys through.
+#[derive(Debug)]
+pub struct Prefixed<S> {
+    store: S,
+    prefix: String,
+}
+
+impl<S> Prefixed<S> {
+    pub fn new(store: S, prefix: String) -> Prefixed<S> {
+        Prefixed { store, prefix }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+#[async_trait]
+impl<S: KeyValueStore + Send> KeyValueStore for Prefixed<S> {
+    async fn get(&mut self, key: &str) -> RpcResult<Option<String>> {
+        let key = self.key(key);
+        self.store.get(&key).await
+    }
+
+    async fn set(
+        &mut self,
+        key: &str,
+        value: &str,
+        expires: Duration,
+    ) -> RpcResult<()> {
+        let key = self.key(key);
+        self.store.set(&key, value, expires).await
+    }
+
+    async fn set_if_absent(
+        &mut self,
+        key: &str,
+        value: &str,
+        expires: Duration,
+    ) -> RpcResult<bool> {
+        let key = self.key(key);
+        self.store.set_if_absent(&key, value, expires).await
+    }
+
+    async fn incr_by(&mut self, key: &str, value: i32) -> RpcResult<i32> {
+        let key = self.key(key);
+        self.store.incr_by(&key, value).await
+    }
+
+    async fn del(&mut self, key: &str) -> RpcResult<bool> {
+        let key = self.key(key);
+        self.store.del(&key).await
+    }
+
+    async fn list_push(&mut self, list: &str, value: &str) -> RpcResult<u32> {
+        let list = self.key(list);
+        self.store.list_push(&list, value).await
+    }
+
+    async fn list_pop(&mut self, list: &str) -> RpcResult<Option<String>> {
+        let list = self.key(list);
+        self.store.list_pop(&list).await
+    }
+
+    async fn list_range(
+        &mut self,
+        list: &str,
+        start: i32,
+        stop: i32,
+    ) -> RpcResult<Vec<String>> {
+        let list = self.key(list);
+        self.store.list_range(&list, start, stop).await
+    }
+
+    async fn list_del(&mut self, list: &str, value: &str) -> RpcResult<bool> {
+        let list = self.key(list);
+        self.store.list_del(&list, value).await
+    }
+
+    // Each tenant has secrets of its own, such as its Twilio account.
+    async fn get_secret(&mut self, key: &str) -> RpcResult<Option<String>> {
+        let key = self.key(key);
+        self.store.get_secret(&key).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_value_store::InMemory;
+
+    #[tokio::test]
+    async fn it_keeps_prefixes_apart() {
+        let forever = Duration::ZERO;
+        let mut a = Prefixed::new(InMemory::new(), "a:".to_string());
+        a.set("key", "a", forever).await.unwrap();
+        a.list_push("list", "a").await.unwrap();
+
+        // Another prefix over the same store sees none of it.
+        let mut b = Prefixed::new(a.store, "b:".to_string());
+        assert_eq!(b.get("key").await.unwrap(), None);
+        assert!(b.set_if_absent("key", "b", forever).await.unwrap());
+        assert_eq!(b.incr_by("count", 2).await.unwrap(), 2);
+        assert_eq!(b.list_pop("list").await.unwrap(), None);
+
+        let mut store = b.store;
+        assert_eq!(store.get("a:key").await.unwrap().unwrap(), "a");
+        assert_eq!(store.get("b:key").await.unwrap().unwrap(), "b");
+        assert_eq!(store.get("key").await.unwrap(), None);
+        let list = store.list_range("a:list", 0, -1).await.unwrap();
+        assert_eq!(list, vec!["a"]);
+
+        // Secrets too.
+        store.set_secret("a:secret", "a");
+        let mut b = Prefixed::new(store, "b:".to_string());
+        assert_eq!(b.get_secret("secret").await.unwrap(), None);
+        let mut a = Prefixed::new(b.store, "a:".to_string());
+        assert_eq!(a.get_secret("secret").await.unwrap().unwrap(), "a");
+    }
+}
@@ -2,7 +2,12 @@ use wasmbus_rpc::actor::prelude::*;
 use wasmcloud_interface_httpserver::{
     HttpRequest, HttpResponse, HttpServer, HttpServerReceiver,
 };
+mod classifier;
+use classifier::Classifier;
+mod clock;
+mod config;
 mod logger;
+mod random;
 
 #[derive(Debug, Default, Actor, HealthResponder)]
 #[services(Actor, HttpServer)]
@@ -24,18 +29,13 @@ use serde_urlencoded as urlencoded;
 
 use key_value_store::KeyValueStore;
 
+mod http_client;
+use http_client::HttpClient;
 mod key_value_store;
 mod responder;
-mod twilio {
-    use serde::Deserialize;
-
-    #[derive(Debug, Deserialize)]
-    #[serde(rename_all = "PascalCase")]
-    pub struct Payload {
-        pub body: String,
-        pub from: String,
-    }
-}
+mod tenant;
+use tenant::Tenant;
+mod twilio;
 
 async fn handle_http_request(
     ctx: &Context,
@@ -44,7 +44,21 @@ async fn handle_http_request(
     let payload = &req.body.as_slice();
     let payload: twilio::Payload = urlencoded::from_bytes(payload).unwrap();
     let mut store = new_store(ctx);
-    let body = respond(payload.body, payload.from, &mut store).await;
+    let tenant = Tenant::resolve(&payload.to, &mut store).await?;
+    let mut store = key_value_store::Prefixed::new(store, tenant.prefix());
+    let mut client = new_http_client(ctx);
+    let signature = twilio::Signature::new(req);
+    let mut classifier = new_classifier(ctx);
+    let (prompt, prompter) = (payload.body, payload.from);
+    let body = respond(
+        prompt,
+        prompter,
+        &signature,
+        &mut store,
+        &mut client,
+        &mut classifier,
+    )
+    .await;
 
     let mut resp = HttpResponse {
         body: body.as_bytes().to_vec(),
@@ -61,19 +75,26 @@ async fn handle_http_request(
 }
 
 #[cfg(not(test))]
-async fn respond<T: KeyValueStore>(
+async fn respond<T: KeyValueStore, C: HttpClient, K: Classifier>(
     prompt: String,
     prompter: String,
+    signature: &twilio::Signature,
     store: &mut T,
+    client: &mut C,
+    classifier: &mut K,
 ) -> String {
-    responder::handle(prompt, prompter, store).await
+    responder::handle(prompt, prompter, signature, store, client, classifier)
+        .await
 }
 
 #[cfg(test)]
-async fn respond<T: KeyValueStore>(
+async fn respond<T: KeyValueStore, C: HttpClient, K: Classifier>(
     prompt: String,
     prompter: String,
+    _signature: &twilio::Signature,
     _store: &mut T,
+    _client: &mut C,
+    _classifier: &mut K,
 ) -> String {
     format!("from: {}, body: {}", prompter, prompt)
 }
@@ -88,6 +109,22 @@ fn new_store(_ctx: &Context) -> key_value_store::InMemory {
     key_value_store::InMemory::new()
 }
 
+#[cfg(target_arch = "wasm32")]
+fn new_http_client(ctx: &Context) -> http_client::Actor<'_> {
+    http_client::Actor::new(ctx)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn new_http_client(_ctx: &Context) -> http_client::Tcp {
+    http_client::Tcp::new()
+}
+
+// No classifier service is deployed yet, so only the configured lists
+// moderate messages.
+fn new_classifier(_ctx: &Context) -> classifier::Permissive {
+    classifier::Permissive::new()
+}
+
 #[cfg(test)]
 mod test {
     use crate::handle_http_request;
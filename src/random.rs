@@ -0,0 +1,88 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use wasmbus_rpc::actor::prelude::*;
+
+// Doesn't depend on an OS entropy source, so it's available on wasm32.
+pub type Rng = ChaCha8Rng;
+
+// The operation on the host's number generator that gives a random (v4) UUID.
+pub const GENERATE_GUID_METHOD: &str = "NumberGen.GenerateGuid";
+
+// Tests are repeatable with a seed of their own.
+#[cfg(test)]
+pub fn from_seed(seed: u64) -> Rng {
+    Rng::seed_from_u64(seed)
+}
+
+// Actors have no entropy source of their own, and the time and anything kept
+// in the store can be guessed, so the seed is asked of the host.
+#[cfg(target_arch = "wasm32")]
+pub async fn new() -> RpcResult<Rng> {
+    use std::borrow::Cow;
+    use wasmbus_rpc::common::Message;
+
+    let transport =
+        WasmHost::to_provider("wasmcloud:builtin:numbergen", "default")?;
+    let response = transport
+        .send(
+            &Context::default(),
+            Message {
+                method: GENERATE_GUID_METHOD,
+                arg: Cow::Borrowed(b""),
+            },
+            None,
+        )
+        .await?;
+    let guid: String = wasmbus_rpc::common::deserialize(&response)
+        .map_err(|error| RpcError::Deser(format!("'{}': String", error)))?;
+
+    Ok(Rng::from_seed(seed_from_guid(&guid)?))
+}
+
+// Everything else has an entropy source to seed from.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn new() -> RpcResult<Rng> {
+    Ok(Rng::from_entropy())
+}
+
+// All of a GUID's hex digits, whatever the hyphenation, which leaves the 122
+// random bits of a v4 one in the seed.
+pub fn seed_from_guid(guid: &str) -> RpcResult<[u8; 32]> {
+    let digits: String = guid.chars().filter(|c| *c != '-').collect();
+    let value = match u128::from_str_radix(&digits, 16) {
+        Ok(value) if digits.len() == 32 => value,
+        _ => return Err(RpcError::Deser(format!("guid: {}", guid))),
+    };
+
+    let mut seed = [0; 32];
+    seed[..16].copy_from_slice(&value.to_le_bytes());
+    Ok(seed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng as _;
+
+    #[tokio::test]
+    async fn it_seeds_apart_at_the_same_time() {
+        tokio::time::pause();
+        let (mut first, mut second) =
+            (new().await.unwrap(), new().await.unwrap());
+        assert_ne!(first.gen::<u64>(), second.gen::<u64>());
+    }
+
+    #[test]
+    fn it_seeds_from_guids() {
+        let guid = "9b2f4c1e-7d3a-4e8b-a6f0-1c5d2e3f4a5b";
+        let seed = seed_from_guid(guid).unwrap();
+        assert_eq!(seed[0], 0x5b);
+        assert_eq!(seed[15], 0x9b);
+        assert_eq!(seed[16..], [0; 16]);
+        assert_ne!(seed, seed_from_guid(&guid.replace('9', "8")).unwrap());
+
+        for guid in ["", "not a guid", "9b2f4c1e-7d3a-4e8b"] {
+            assert!(seed_from_guid(guid).is_err());
+        }
+    }
+}
@@ -1,59 +1,255 @@
+use crate::classifier::Classifier;
+use crate::clock;
+use crate::config::Config;
+use crate::http_client::HttpClient;
 use crate::key_value_store::KeyValueStore;
+use wasmbus_rpc::actor::prelude::RpcResult;
 
 use crate::logger;
+use crate::twilio;
+mod admin;
 mod exchange;
 mod messages;
-mod parser;
+mod moderation;
+pub mod parser;
+mod segments;
 
-pub async fn handle<T: KeyValueStore>(
+pub async fn handle<T: KeyValueStore, C: HttpClient, K: Classifier>(
     prompt: String,
     prompter: String,
+    signature: &twilio::Signature,
     store: &mut T,
+    client: &mut C,
+    classifier: &mut K,
 ) -> String {
     logger::log(format!("prompter: {}, prompt: {}", prompter, prompt)).await;
 
+    // Deployment config applies to whatever the prompt turns out to be, down
+    // to the words it's made of.
+    let config = match Config::load(store).await {
+        Ok(config) => config,
+        Err(error) => {
+            logger::log(format!("config error: {:?}", error)).await;
+            return messages::unknown_error();
+        }
+    };
+
+    // Every reply is kept within the budget here, whatever it's for.
+    let response = respond(
+        prompt, prompter, signature, &config, store, client, classifier,
+    )
+    .await;
+    segments::fit(&response, config.reply_segments)
+}
+
+async fn respond<T: KeyValueStore, C: HttpClient, K: Classifier>(
+    prompt: String,
+    prompter: String,
+    signature: &twilio::Signature,
+    config: &Config,
+    store: &mut T,
+    client: &mut C,
+    classifier: &mut K,
+) -> String {
+    // Admins are never turned away, so that they can always undo either.
+    let admin = is_admin(&prompter, signature, config);
+    if !admin {
+        match turned_away(&prompter, store).await {
+            Ok(Some(response)) => return response,
+            Ok(None) => {}
+            Err(error) => {
+                logger::log(format!("admin error: {:?}", error)).await;
+                return messages::unknown_error();
+            }
+        }
+    }
+
+    let parsed = parser::parse(prompt, config);
+
+    // Messages are moderated before any code is taken for them, and replies
+    // before they're passed on.
+    if let Ok(action) = &parsed {
+        if let Some(response) =
+            moderation_response(action, config, classifier).await
+        {
+            return response;
+        }
+    }
+
+    // The time costs a request to the host, so it's only asked for by the
+    // actions that look at it.
+    if let Ok(action) = &parsed {
+        if action.needs_time() {
+            if let Err(error) = clock::sync(client).await {
+                logger::log(format!("clock error: {:?}", error)).await;
+                return messages::unknown_error();
+            }
+        }
+    }
+
     // Prompt can either parse successfully or not.
-    match parser::parse(prompt) {
+    match parsed {
         // When prompt does parse correctly it is for one of a distinct set of
         // actions.
         Ok(action) => match action {
             // Prompt indicates that a code should be created for some message.
             parser::Action::Create(message) => {
-                let result = create(message, store).await;
+                let options = exchange::CreateOptions::default();
+                let result =
+                    create(message, prompter, options, config, store).await;
+                create_response(config, result).await
+            }
+
+            // Prompt indicates that a code should be created for some message
+            // that can only be read with a PIN.
+            parser::Action::CreateWithPin(pin, message) => {
+                let options = exchange::CreateOptions {
+                    pin: Some(pin),
+                    ..exchange::CreateOptions::default()
+                };
+                let result =
+                    create(message, prompter, options, config, store).await;
+                create_response(config, result).await
+            }
+
+            // Prompt indicates that a code should be created for some message
+            // whose creator is told when it's read.
+            parser::Action::CreateWithReceipt(message) => {
+                let options = exchange::CreateOptions {
+                    receipts: true,
+                    ..exchange::CreateOptions::default()
+                };
+                let result =
+                    create(message, prompter, options, config, store).await;
+                create_response(config, result).await
+            }
+
+            // Prompt indicates that a code should be created for some message
+            // that can't be read until later.
+            parser::Action::CreateWithReveal(reveal_after, message) => {
+                let options = exchange::CreateOptions {
+                    reveal_after: Some(reveal_after),
+                    ..exchange::CreateOptions::default()
+                };
+                let result =
+                    create(message, prompter, options, config, store).await;
+                create_response(config, result).await
+            }
+
+            // Prompt indicates that a code should be created for some message
+            // that only certain numbers can read.
+            parser::Action::CreateFor(recipients, message) => {
+                let options = exchange::CreateOptions {
+                    recipients,
+                    ..exchange::CreateOptions::default()
+                };
+                let result =
+                    create(message, prompter, options, config, store).await;
+                create_response(config, result).await
+            }
+
+            // Prompt indicates that a code of the prompter's choosing should
+            // be created for some message.
+            parser::Action::CreateAs(code, message) => {
+                let options = exchange::CreateOptions::default();
+                let result =
+                    create_as(code, message, prompter, options, config, store)
+                        .await;
+                create_response(config, result).await
+            }
+
+            // Prompt indicates that a code should be read.
+            parser::Action::Read(code, pin) => {
+                let result =
+                    find(code, pin, prompter, config, store, client).await;
+                match result {
+                    // Code exists in the exchange, yielding back the
+                    // corresponding message.
+                    Ok(message) => messages::find_success(config, message),
+                    Err(error) => find_error_response(config, error).await,
+                }
+            }
+
+            // Prompt indicates that the creator of a code should be replied
+            // to.
+            parser::Action::Reply(code, pin, text) => {
+                let result =
+                    reply(code, pin, text, prompter, config, store, client)
+                        .await;
+                match result {
+                    // Reply was texted on to the creator.
+                    Ok(exchange::Delivery::Sent) => messages::reply_sent(),
+                    // Reply was kept for the creator to fetch.
+                    Ok(exchange::Delivery::Queued) => messages::reply_queued(),
+                    Err(error) => find_error_response(config, error).await,
+                }
+            }
+
+            // Prompt indicates that the state of the exchange should be
+            // reported, in more detail to admins.
+            parser::Action::Status => {
+                status_response(admin, config, store).await
+            }
+
+            // Prompt asks what can be done.
+            parser::Action::Help => messages::help(config),
+
+            // Prompt indicates that an admin wants something done. To anyone
+            // else, admin actions don't exist.
+            parser::Action::Admin(action) => {
+                if admin {
+                    admin_response(action, prompter, config, store).await
+                } else {
+                    messages::prompt_malformed_error(config)
+                }
+            }
+
+            // Prompt indicates that replies to the prompter's own codes should
+            // be fetched.
+            parser::Action::Inbox => {
+                let result = inbox(prompter, config, store).await;
                 match result {
-                    // Create is valid, yielding back a code corresponding to
-                    // the message.
-                    Ok(code) => messages::create_success(code),
+                    Ok(inbox) => messages::inbox_success(
+                        config,
+                        inbox
+                            .replies
+                            .into_iter()
+                            .map(|relayed| (relayed.code, relayed.text))
+                            .collect(),
+                        inbox.remaining,
+                    ),
 
                     Err(error) => match error {
-                        // All code words are used up.
-                        exchange::CreateError::OverCapacity => {
-                            messages::create_over_capacity_error()
-                        }
                         // Unknown error.
-                        exchange::CreateError::Unknown(_) => {
-                            messages::create_unknown_error()
+                        exchange::InboxError::Unknown(error) => {
+                            logger::log(format!("inbox error: {:?}", error))
+                                .await;
+                            messages::inbox_unknown_error()
                         }
                     },
                 }
             }
 
-            // Prompt indicates that a code should be read.
-            parser::Action::Read(code) => {
-                let result = find(code, store).await;
+            // Prompt indicates that the prompter's own codes should be listed.
+            parser::Action::List => {
+                let result = list(prompter, store).await;
                 match result {
-                    // Code exists in the exchange, yielding back the
-                    // corresponding message.
-                    Ok(message) => messages::find_success(message),
+                    Ok(listings) => messages::list_success(
+                        config,
+                        listings
+                            .iter()
+                            .map(|listing| {
+                                (listing.code.clone(), listing.expires_in())
+                            })
+                            .collect(),
+                    ),
 
                     Err(error) => match error {
-                        // Code doesn't exist in the exchange.
-                        exchange::FindError::NotFound => {
-                            messages::find_not_found_error()
-                        }
                         // Unknown error.
-                        exchange::FindError::Unknown(_) => {
-                            messages::find_unknown_error()
+                        exchange::ListError::Unknown(error) => {
+                            logger::log(format!("list error: {:?}", error))
+                                .await;
+                            messages::list_unknown_error()
                         }
                     },
                 }
@@ -64,31 +260,315 @@ pub async fn handle<T: KeyValueStore>(
         Err(error) => match error {
             // Prompt is so malformed it fails to indicate any action.
             parser::PromptParseError::MalformedAction => {
-                messages::prompt_malformed_error()
+                messages::prompt_malformed_error(config)
             }
 
             // Prompt indicates a create but message is too long or short.
             parser::PromptParseError::MessageInvalid(reason) => {
-                messages::prompt_create_message_invalid_error(reason)
+                messages::prompt_create_message_invalid_error(config, reason)
+            }
+        },
+    }
+}
+
+async fn create_response(
+    config: &Config,
+    result: exchange::CreateResult,
+) -> String {
+    match result {
+        // Create is valid, yielding back a code corresponding to the message.
+        Ok(code) => messages::create_success(config, code),
+
+        Err(error) => match error {
+            // All code words are used up.
+            exchange::CreateError::OverCapacity => {
+                messages::create_over_capacity_error(config)
+            }
+            // Chosen code is in use.
+            exchange::CreateError::Taken => messages::create_taken_error(),
+            // Chosen code can't be used as a code.
+            exchange::CreateError::NotCodeWord => {
+                messages::create_not_code_word_error()
+            }
+            // Message would be held back past its expiry.
+            exchange::CreateError::RevealTooLate => {
+                messages::create_reveal_too_late_error()
+            }
+            // Message was turned down by moderation.
+            exchange::CreateError::Refused => messages::create_refused_error(),
+            // Unknown error.
+            exchange::CreateError::Unknown(error) => {
+                logger::log(format!("create error: {:?}", error)).await;
+                messages::create_unknown_error()
+            }
+        },
+    }
+}
+
+// Numbers are easily spoofed, so admins' requests have to have been signed by
+// twilio too.
+fn is_admin(
+    prompter: &str,
+    signature: &twilio::Signature,
+    config: &Config,
+) -> bool {
+    if !config.is_admin(prompter) {
+        return false;
+    }
+
+    match &config.twilio {
+        Some(account) => signature.is_valid(account),
+        None => false,
+    }
+}
+
+// Banned numbers, and everyone during maintenance, are told so whatever they
+// send.
+async fn turned_away<T: KeyValueStore>(
+    prompter: &str,
+    store: &mut T,
+) -> RpcResult<Option<String>> {
+    if admin::is_banned(prompter, store).await? {
+        Ok(Some(messages::banned_error()))
+    } else if admin::in_maintenance(store).await? {
+        Ok(Some(messages::maintenance_error()))
+    } else {
+        Ok(None)
+    }
+}
+
+async fn status_response<T: KeyValueStore>(
+    admin: bool,
+    config: &Config,
+    store: &mut T,
+) -> String {
+    let status = match status(config, store).await {
+        Ok(status) => status,
+        Err(error) => match error {
+            // Unknown error.
+            exchange::StatusError::Unknown(error) => {
+                logger::log(format!("status error: {:?}", error)).await;
+                return messages::status_unknown_error();
             }
         },
+    };
+
+    let next_free_in = status
+        .next_expires_at
+        .map(|at| at.saturating_sub(crate::clock::now()));
+    let summary =
+        messages::status_success(status.free(), status.in_use, next_free_in);
+    if !admin {
+        return summary;
+    }
+
+    let detail = messages::status_detail(
+        &status.codebook,
+        status.codebook_size,
+        config.code_words,
+        status.capacity,
+        status.free_listed,
+        status.used_listed,
+        status.fresh,
+    );
+    format!("{}\n\n{}", summary, detail)
+}
+
+// Every admin action is audited before it's taken, and isn't taken if it
+// can't be.
+async fn admin_response<T: KeyValueStore>(
+    action: parser::AdminAction,
+    admin: String,
+    config: &Config,
+    store: &mut T,
+) -> String {
+    if let Err(error) = admin::audit(&admin, &action, store).await {
+        logger::log(format!("audit error: {:?}", error)).await;
+        return messages::admin_unknown_error();
+    }
+
+    let result = match action {
+        parser::AdminAction::Purge(code) => {
+            match purge(code.clone(), store).await {
+                Ok(purged) => Ok(messages::admin_purged(&code, purged)),
+                Err(exchange::PurgeError::Unknown(error)) => Err(error),
+            }
+        }
+        parser::AdminAction::Ban(number) => admin::ban(&number, store)
+            .await
+            .map(|()| messages::admin_banned(&number)),
+        parser::AdminAction::Unban(number) => admin::unban(&number, store)
+            .await
+            .map(|banned| messages::admin_unbanned(&number, banned)),
+        parser::AdminAction::Stats => {
+            Ok(status_response(true, config, store).await)
+        }
+        parser::AdminAction::Maintenance(on) => {
+            admin::set_maintenance(on, store)
+                .await
+                .map(|()| messages::admin_maintenance(on))
+        }
+    };
+
+    match result {
+        Ok(response) => response,
+        Err(error) => {
+            logger::log(format!("admin error: {:?}", error)).await;
+            messages::admin_unknown_error()
+        }
+    }
+}
+
+// Nothing when the action passes moderation, or has nothing to moderate.
+async fn moderation_response<K: Classifier>(
+    action: &parser::Action,
+    config: &Config,
+    classifier: &mut K,
+) -> Option<String> {
+    let text = action.passed_on_text()?;
+    let moderation = &config.moderation;
+    let error = match moderation::moderate(text, moderation, classifier).await {
+        Ok(None) => return None,
+        Ok(Some(refusal)) => {
+            logger::log(format!("refused: {:?}", refusal)).await;
+            None
+        }
+        Err(error) => Some(error),
+    };
+
+    let response = match (action, error) {
+        (parser::Action::Reply(..), None) => messages::reply_refused_error(),
+        (parser::Action::Reply(..), Some(error)) => {
+            let error = exchange::FindError::Unknown(error);
+            find_error_response(config, error).await
+        }
+        (_, None) => {
+            let error = exchange::CreateError::Refused;
+            create_response(config, Err(error)).await
+        }
+        (_, Some(error)) => {
+            let error = exchange::CreateError::Unknown(error);
+            create_response(config, Err(error)).await
+        }
+    };
+    Some(response)
+}
+
+// Codes that can't be found can't be replied to either, for the same reasons.
+async fn find_error_response(
+    config: &Config,
+    error: exchange::FindError,
+) -> String {
+    match error {
+        // Code doesn't exist in the exchange anymore.
+        exchange::FindError::Expired => messages::find_expired_error(config),
+        // Code could exist in the exchange but doesn't.
+        exchange::FindError::Inactive => messages::find_inactive_error(config),
+        // Code couldn't ever exist in the exchange.
+        exchange::FindError::NotCodeWord => {
+            messages::find_not_code_word_error(config)
+        }
+        // Code needs a PIN that wasn't given.
+        exchange::FindError::WrongPin => messages::find_wrong_pin_error(),
+        // Code has had too many wrong PINs.
+        exchange::FindError::LockedOut => messages::find_locked_out_error(),
+        // Reader has replied to the code too often lately.
+        exchange::FindError::TooManyReplies => messages::reply_too_many_error(),
+        // Code's message is being held back for now.
+        exchange::FindError::Unrevealed(reveal_at) => {
+            messages::find_unrevealed_error(reveal_at)
+        }
+        // Code is misspelt, but it's unclear what it was meant to be.
+        exchange::FindError::Ambiguous(codes) => {
+            messages::find_ambiguous_error(codes)
+        }
+        // Unknown error.
+        exchange::FindError::Unknown(error) => {
+            logger::log(format!("find error: {:?}", error)).await;
+            messages::find_unknown_error()
+        }
     }
 }
 
 #[cfg(not(test))]
 async fn create<T: KeyValueStore>(
     message: String,
+    creator: String,
+    options: exchange::CreateOptions,
+    config: &Config,
     store: &mut T,
 ) -> Result<String, exchange::CreateError> {
-    exchange::create(message, store).await
+    exchange::create(message, creator, options, config, store).await
 }
 
 #[cfg(not(test))]
-async fn find<T: KeyValueStore>(
+async fn create_as<T: KeyValueStore>(
     code: String,
+    message: String,
+    creator: String,
+    options: exchange::CreateOptions,
+    config: &Config,
+    store: &mut T,
+) -> Result<String, exchange::CreateError> {
+    exchange::create_as(code, message, creator, options, config, store).await
+}
+
+#[cfg(not(test))]
+async fn find<T: KeyValueStore, C: HttpClient>(
+    code: String,
+    pin: Option<String>,
+    reader: String,
+    config: &Config,
     store: &mut T,
+    client: &mut C,
 ) -> Result<String, exchange::FindError> {
-    exchange::find(code, store).await
+    exchange::find(code, pin, reader, config, store, client).await
+}
+
+#[cfg(not(test))]
+async fn reply<T: KeyValueStore, C: HttpClient>(
+    code: String,
+    pin: Option<String>,
+    text: String,
+    reader: String,
+    config: &Config,
+    store: &mut T,
+    client: &mut C,
+) -> Result<exchange::Delivery, exchange::FindError> {
+    exchange::reply(code, pin, text, reader, config, store, client).await
+}
+
+#[cfg(not(test))]
+async fn list<T: KeyValueStore>(
+    creator: String,
+    store: &mut T,
+) -> Result<Vec<exchange::Listing>, exchange::ListError> {
+    exchange::list(creator, store).await
+}
+
+#[cfg(not(test))]
+async fn purge<T: KeyValueStore>(
+    code: String,
+    store: &mut T,
+) -> Result<bool, exchange::PurgeError> {
+    exchange::purge(code, store).await
+}
+
+#[cfg(not(test))]
+async fn status<T: KeyValueStore>(
+    config: &Config,
+    store: &mut T,
+) -> Result<exchange::Status, exchange::StatusError> {
+    exchange::status(config, store).await
+}
+
+#[cfg(not(test))]
+async fn inbox<T: KeyValueStore>(
+    creator: String,
+    config: &Config,
+    store: &mut T,
+) -> Result<exchange::Inbox, exchange::InboxError> {
+    exchange::inbox(creator, config, store).await
 }
 
 #[cfg(test)]
@@ -99,6 +579,9 @@ use wasmbus_rpc::actor::prelude::*;
 #[cfg(test)]
 async fn create<T: KeyValueStore>(
     message: String,
+    _creator: String,
+    _options: exchange::CreateOptions,
+    _config: &Config,
     _store: &mut T,
 ) -> Result<String, exchange::CreateError> {
     match message.as_str() {
@@ -107,6 +590,7 @@ async fn create<T: KeyValueStore>(
             valid message
             spanning lines"} => Ok("validcode".to_string()),
         "over capacity" => Err(exchange::CreateError::OverCapacity),
+        "revealed too late" => Err(exchange::CreateError::RevealTooLate),
         "unknown error" => Err(exchange::CreateError::Unknown(
             RpcError::Other("unknown".to_string()),
         )),
@@ -115,13 +599,53 @@ async fn create<T: KeyValueStore>(
 }
 
 #[cfg(test)]
-async fn find<T: KeyValueStore>(
+async fn create_as<T: KeyValueStore>(
     code: String,
+    _message: String,
+    _creator: String,
+    _options: exchange::CreateOptions,
+    _config: &Config,
     _store: &mut T,
-) -> Result<String, exchange::FindError> {
+) -> Result<String, exchange::CreateError> {
     match code.as_str() {
+        "validcode" => Ok("validcode".to_string()),
+        "takencode" => Err(exchange::CreateError::Taken),
+        "notcodeword" => Err(exchange::CreateError::NotCodeWord),
+        _ => panic!(),
+    }
+}
+
+#[cfg(test)]
+async fn find<T: KeyValueStore, C: HttpClient>(
+    code: String,
+    pin: Option<String>,
+    _reader: String,
+    _config: &Config,
+    _store: &mut T,
+    _client: &mut C,
+) -> Result<String, exchange::FindError> {
+    match (code.as_str(), pin.as_deref()) {
+        ("pinnedcode", Some("4821")) => Ok("pinned message".to_string()),
+        ("pinnedcode", _) => Err(exchange::FindError::WrongPin),
+        ("lockedcode", _) => Err(exchange::FindError::LockedOut),
+        (code, _) => find_unpinned(code),
+    }
+}
+
+#[cfg(test)]
+fn find_unpinned(code: &str) -> Result<String, exchange::FindError> {
+    match code {
         "foundcode" => Ok("found message".to_string()),
-        "notfoundcode" => Err(exchange::FindError::NotFound),
+        "expiredcode" => Err(exchange::FindError::Expired),
+        "inactivecode" => Err(exchange::FindError::Inactive),
+        "unrevealedcode" => Err(exchange::FindError::Unrevealed(
+            tokio::time::Duration::from_secs(3_600),
+        )),
+        "notcodeword" => Err(exchange::FindError::NotCodeWord),
+        "ambiguouscode" => Err(exchange::FindError::Ambiguous(vec![
+            "boat".to_string(),
+            "coat".to_string(),
+        ])),
         "unknownerror" => Err(exchange::FindError::Unknown(RpcError::Other(
             "unknown".to_string(),
         ))),
@@ -129,20 +653,126 @@ async fn find<T: KeyValueStore>(
     }
 }
 
+#[cfg(test)]
+async fn reply<T: KeyValueStore, C: HttpClient>(
+    code: String,
+    _pin: Option<String>,
+    _text: String,
+    _reader: String,
+    _config: &Config,
+    _store: &mut T,
+    _client: &mut C,
+) -> Result<exchange::Delivery, exchange::FindError> {
+    match code.as_str() {
+        "sentcode" => Ok(exchange::Delivery::Sent),
+        "queuedcode" => Ok(exchange::Delivery::Queued),
+        "limitedcode" => Err(exchange::FindError::TooManyReplies),
+        code => find_unpinned(code).map(|_| panic!()),
+    }
+}
+
+#[cfg(test)]
+async fn list<T: KeyValueStore>(
+    creator: String,
+    _store: &mut T,
+) -> Result<Vec<exchange::Listing>, exchange::ListError> {
+    match creator.as_str() {
+        "lister" => Ok(vec![exchange::Listing {
+            code: "listedcode".to_string(),
+            expires_at: crate::clock::now()
+                + tokio::time::Duration::from_secs(90),
+        }]),
+        "unknownerror" => Err(exchange::ListError::Unknown(RpcError::Other(
+            "unknown".to_string(),
+        ))),
+        _ => panic!(),
+    }
+}
+
+#[cfg(test)]
+async fn purge<T: KeyValueStore>(
+    code: String,
+    _store: &mut T,
+) -> Result<bool, exchange::PurgeError> {
+    match code.as_str() {
+        "foundcode" => Ok(true),
+        "inactivecode" => Ok(false),
+        "unknownerror" => Err(exchange::PurgeError::Unknown(RpcError::Other(
+            "unknown".to_string(),
+        ))),
+        _ => panic!(),
+    }
+}
+
+#[cfg(test)]
+async fn status<T: KeyValueStore>(
+    config: &Config,
+    _store: &mut T,
+) -> Result<exchange::Status, exchange::StatusError> {
+    match config.codebook.as_str() {
+        "basic" => Ok(exchange::Status {
+            capacity: 100,
+            in_use: 40,
+            next_expires_at: Some(
+                crate::clock::now() + tokio::time::Duration::from_secs(90),
+            ),
+            codebook: "basic".to_string(),
+            codebook_size: 10,
+            free_listed: 5,
+            used_listed: 45,
+            fresh: 50,
+        }),
+        "unknownerror" => Err(exchange::StatusError::Unknown(RpcError::Other(
+            "unknown".to_string(),
+        ))),
+        _ => panic!(),
+    }
+}
+
+#[cfg(test)]
+async fn inbox<T: KeyValueStore>(
+    creator: String,
+    _config: &Config,
+    _store: &mut T,
+) -> Result<exchange::Inbox, exchange::InboxError> {
+    match creator.as_str() {
+        "inboxer" => Ok(exchange::Inbox {
+            replies: vec![exchange::Relayed {
+                code: "inboxcode".to_string(),
+                text: "inbox reply".to_string(),
+            }],
+            remaining: 2,
+        }),
+        "unknownerror" => Err(exchange::InboxError::Unknown(RpcError::Other(
+            "unknown".to_string(),
+        ))),
+        _ => panic!(),
+    }
+}
+
 #[cfg(test)]
 pub mod test {
-    use crate::{key_value_store, responder::*};
+    use crate::{classifier, http_client, key_value_store, responder::*};
 
     #[tokio::test]
     async fn create_success() {
         let response = handle(
             "partyskunk valid message".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::create_success("validcode".to_string()))
+        assert_eq!(
+            response,
+            messages::create_success(
+                &Config::default(),
+                "validcode".to_string()
+            )
+        )
     }
 
     #[tokio::test]
@@ -154,11 +784,20 @@ pub mod test {
             "}
             .to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::create_success("validcode".to_string()))
+        assert_eq!(
+            response,
+            messages::create_success(
+                &Config::default(),
+                "validcode".to_string()
+            )
+        )
     }
 
     #[tokio::test]
@@ -166,94 +805,877 @@ pub mod test {
         let response = handle(
             "partyskunk over capacity".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::create_over_capacity_error())
+        assert_eq!(
+            response,
+            messages::create_over_capacity_error(&Config::default())
+        )
     }
 
     #[tokio::test]
-    async fn create_unknown_error() {
+    async fn create_as_success() {
         let response = handle(
-            "partyskunk unknown error".to_string(),
+            "partyskunk as validcode: valid message".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::create_unknown_error())
+        assert_eq!(
+            response,
+            messages::create_success(
+                &Config::default(),
+                "validcode".to_string()
+            )
+        )
     }
 
     #[tokio::test]
-    async fn find_success() {
+    async fn create_for_success() {
         let response = handle(
-            "foundcode".to_string(),
+            "partyskunk for +14105551234 valid message".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
         assert_eq!(
             response,
-            messages::find_success("found message".to_string())
+            messages::create_success(
+                &Config::default(),
+                "validcode".to_string()
+            )
         )
     }
 
     #[tokio::test]
-    async fn find_not_found_error() {
+    async fn create_taken_error() {
         let response = handle(
-            "notfoundcode".to_string(),
+            "partyskunk as takencode: valid message".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::find_not_found_error())
+        assert_eq!(response, messages::create_taken_error())
     }
 
     #[tokio::test]
-    async fn find_unknown_error() {
+    async fn create_not_code_word_error() {
         let response = handle(
-            "unknownerror".to_string(),
+            "partyskunk as notcodeword: valid message".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::find_unknown_error())
+        assert_eq!(response, messages::create_not_code_word_error())
     }
 
     #[tokio::test]
-    async fn prompt_malformed() {
+    async fn create_reveal_too_late_error() {
         let response = handle(
-            "partyskunk!".to_string(),
+            "partyskunk after 25h revealed too late".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
-        assert_eq!(response, messages::prompt_malformed_error());
+        assert_eq!(response, messages::create_reveal_too_late_error())
     }
 
     #[tokio::test]
-    async fn prompt_create_message_invalid_error() {
+    async fn create_refused_error() {
+        let mut store = mock_key_value_store();
+        store
+            .set(
+                "partyskunk:config",
+                r#"{"moderation": {"blocklist": ["blocked"]}}"#,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        for prompt in [
+            "partyskunk classified message",
+            "partyskunk pin 4821 a Blocked message",
+            "partyskunk as validcode blocked",
+        ] {
+            let response = handle(
+                prompt.to_string(),
+                "prompter".to_string(),
+                &mock_signature(),
+                &mut store,
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(response, messages::create_refused_error())
+        }
+
+        // Replies are moderated too.
         let response = handle(
-            "partyskunk".to_string(),
+            "sentcode reply blocked".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::reply_refused_error())
+    }
+
+    #[tokio::test]
+    async fn create_unknown_error() {
+        let response = handle(
+            "partyskunk unknown error".to_string(),
             "prompter".to_string(),
+            &mock_signature(),
             &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::create_unknown_error())
+    }
+
+    #[tokio::test]
+    async fn find_success() {
+        let response = handle(
+            "foundcode".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
         )
         .await;
 
         assert_eq!(
             response,
-            messages::prompt_create_message_invalid_error(
-                "some invalid reason".to_string()
+            messages::find_success(
+                &Config::default(),
+                "found message".to_string()
             )
         )
     }
 
-    fn mock_key_value_store() -> key_value_store::InMemory {
-        key_value_store::InMemory::new()
+    #[tokio::test]
+    async fn find_expired_error() {
+        let response = handle(
+            "expiredcode".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::find_expired_error(&Config::default()))
+    }
+
+    #[tokio::test]
+    async fn find_inactive_error() {
+        let response = handle(
+            "inactivecode".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::find_inactive_error(&Config::default()))
+    }
+
+    #[tokio::test]
+    async fn find_not_code_word_error() {
+        let response = handle(
+            "notcodeword".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::find_not_code_word_error(&Config::default())
+        )
+    }
+
+    #[tokio::test]
+    async fn find_unrevealed_error() {
+        let response = handle(
+            "unrevealedcode".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::find_unrevealed_error(tokio::time::Duration::from_secs(
+                3_600
+            ))
+        )
+    }
+
+    #[tokio::test]
+    async fn find_pinned_success() {
+        let response = handle(
+            "pinnedcode 4821".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::find_success(
+                &Config::default(),
+                "pinned message".to_string()
+            )
+        )
+    }
+
+    #[tokio::test]
+    async fn find_wrong_pin_error() {
+        for prompt in ["pinnedcode", "pinnedcode 1234"] {
+            let response = handle(
+                prompt.to_string(),
+                "prompter".to_string(),
+                &mock_signature(),
+                &mut mock_key_value_store(),
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(response, messages::find_wrong_pin_error())
+        }
+    }
+
+    #[tokio::test]
+    async fn find_locked_out_error() {
+        let response = handle(
+            "lockedcode 4821".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::find_locked_out_error())
+    }
+
+    #[tokio::test]
+    async fn find_ambiguous_error() {
+        let response = handle(
+            "ambiguouscode".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::find_ambiguous_error(vec![
+                "boat".to_string(),
+                "coat".to_string()
+            ])
+        )
+    }
+
+    #[tokio::test]
+    async fn find_unknown_error() {
+        let response = handle(
+            "unknownerror".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::find_unknown_error())
+    }
+
+    #[tokio::test]
+    async fn prompt_malformed() {
+        let response = handle(
+            "partyskunk!".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::prompt_malformed_error(&Config::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn prompt_create_message_invalid_error() {
+        let response = handle(
+            "partyskunk".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::prompt_create_message_invalid_error(
+                &Config::default(),
+                "some invalid reason".to_string()
+            )
+        )
+    }
+
+    #[tokio::test]
+    async fn list_success() {
+        tokio::time::pause();
+        let response = handle(
+            "partyskunk LIST".to_string(),
+            "lister".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::list_success(
+                &Config::default(),
+                vec![(
+                    "listedcode".to_string(),
+                    tokio::time::Duration::from_secs(90)
+                )]
+            )
+        )
+    }
+
+    #[tokio::test]
+    async fn list_unknown_error() {
+        let response = handle(
+            "partyskunk list".to_string(),
+            "unknownerror".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::list_unknown_error())
+    }
+
+    #[tokio::test]
+    async fn config_unknown_error() {
+        let mut store = mock_key_value_store();
+        store
+            .set("partyskunk:config", "not json", Default::default())
+            .await
+            .unwrap();
+
+        let response = handle(
+            "foundcode".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::unknown_error())
+    }
+
+    #[tokio::test]
+    async fn reply_success() {
+        for (prompt, expected) in [
+            ("sentcode reply valid reply", messages::reply_sent()),
+            ("queuedcode reply valid reply", messages::reply_queued()),
+        ] {
+            let response = handle(
+                prompt.to_string(),
+                "prompter".to_string(),
+                &mock_signature(),
+                &mut mock_key_value_store(),
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(response, expected)
+        }
+    }
+
+    #[tokio::test]
+    async fn reply_expired_error() {
+        let response = handle(
+            "expiredcode reply valid reply".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::find_expired_error(&Config::default()))
+    }
+
+    #[tokio::test]
+    async fn reply_too_many_error() {
+        let response = handle(
+            "limitedcode reply valid reply".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::reply_too_many_error())
+    }
+
+    #[tokio::test]
+    async fn inbox_success() {
+        let response = handle(
+            "partyskunk inbox".to_string(),
+            "inboxer".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::inbox_success(
+                &Config::default(),
+                vec![("inboxcode".to_string(), "inbox reply".to_string())],
+                2
+            )
+        )
+    }
+
+    #[tokio::test]
+    async fn inbox_unknown_error() {
+        let response = handle(
+            "partyskunk inbox".to_string(),
+            "unknownerror".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::inbox_unknown_error())
+    }
+
+    #[tokio::test]
+    async fn configured_create_word() {
+        let mut store = mock_key_value_store();
+        let config = r#"{"create_word": "memo", "create_aliases": ["new"]}"#;
+        store
+            .set("partyskunk:config", config, Default::default())
+            .await
+            .unwrap();
+
+        for prompt in ["memo valid message", "New valid message"] {
+            let response = handle(
+                prompt.to_string(),
+                "prompter".to_string(),
+                &mock_signature(),
+                &mut store,
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(
+                response,
+                messages::create_success(
+                    &Config::default(),
+                    "validcode".to_string()
+                )
+            )
+        }
+
+        let response = handle(
+            "memo".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            messages::prompt_create_message_invalid_error(
+                &Config {
+                    create_word: "memo".to_string(),
+                    ..Config::default()
+                },
+                "some invalid reason".to_string()
+            )
+        )
+    }
+
+    #[tokio::test]
+    async fn help_success() {
+        let response = handle(
+            "partyskunk help".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut mock_key_value_store(),
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::help(&Config::default()))
+    }
+
+    #[tokio::test]
+    async fn replies_fit_the_segment_budget() {
+        let mut store = mock_key_value_store();
+        let config = r#"{"reply_segments": 1}"#;
+        store
+            .set("partyskunk:config", config, Default::default())
+            .await
+            .unwrap();
+
+        let response = handle(
+            "partyskunk help".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(segments::count(&response), 1);
+        assert!(response.ends_with("..."));
+    }
+
+    #[tokio::test]
+    async fn configured_templates() {
+        let mut store = mock_key_value_store();
+        let config = r#"{"templates": {"create_success": "Blue: {code}"}}"#;
+        store
+            .set("partyskunk:config", config, Default::default())
+            .await
+            .unwrap();
+
+        let response = handle(
+            "partyskunk valid message".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, "Blue: validcode")
+    }
+
+    #[tokio::test]
+    async fn status_success() {
+        tokio::time::pause();
+        let mut store = mock_admin_store().await;
+        let summary = messages::status_success(
+            60,
+            40,
+            Some(tokio::time::Duration::from_secs(90)),
+        );
+
+        let response = handle(
+            "partyskunk status".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+        assert_eq!(response, summary);
+
+        let response = handle(
+            "partyskunk status".to_string(),
+            "admin".to_string(),
+            &mock_admin_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+        let detail = messages::status_detail("basic", 10, 2, 100, 5, 45, 50);
+        assert_eq!(response, format!("{}\n\n{}", summary, detail));
+    }
+
+    #[tokio::test]
+    async fn status_unknown_error() {
+        let mut store = mock_key_value_store();
+        store
+            .set(
+                "partyskunk:config",
+                r#"{"codebook": "unknownerror"}"#,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let response = handle(
+            "partyskunk status".to_string(),
+            "prompter".to_string(),
+            &mock_signature(),
+            &mut store,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::status_unknown_error())
+    }
+
+    #[tokio::test]
+    async fn admin_success() {
+        let mut store = mock_admin_store().await;
+        for (prompt, expected) in [
+            (
+                "partyskunk admin purge foundcode",
+                messages::admin_purged("foundcode", true),
+            ),
+            (
+                "partyskunk admin purge inactivecode",
+                messages::admin_purged("inactivecode", false),
+            ),
+            (
+                "partyskunk admin ban +14105551234",
+                messages::admin_banned("+14105551234"),
+            ),
+            (
+                "partyskunk admin unban +14105551234",
+                messages::admin_unbanned("+14105551234", true),
+            ),
+            (
+                "partyskunk admin maintenance off",
+                messages::admin_maintenance(false),
+            ),
+        ] {
+            let response = handle(
+                prompt.to_string(),
+                "admin".to_string(),
+                &mock_admin_signature(),
+                &mut store,
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(response, expected)
+        }
+
+        let audited = store.list_range("partyskunk:audit", 0, -1).await;
+        assert_eq!(audited.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn admin_unknown_error() {
+        let response = handle(
+            "partyskunk admin purge unknownerror".to_string(),
+            "admin".to_string(),
+            &mock_admin_signature(),
+            &mut mock_admin_store().await,
+            &mut mock_http_client(),
+            &mut mock_classifier(),
+        )
+        .await;
+
+        assert_eq!(response, messages::admin_unknown_error())
+    }
+
+    #[tokio::test]
+    async fn admin_only() {
+        let mut store = mock_admin_store().await;
+        // Admins have to be who they say they are, too.
+        for (prompter, signature) in [
+            ("prompter", mock_admin_signature()),
+            ("admin", mock_signature()),
+        ] {
+            let response = handle(
+                "partyskunk admin ban +14105551234".to_string(),
+                prompter.to_string(),
+                &signature,
+                &mut store,
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            let expected = messages::prompt_malformed_error(&Config::default());
+            assert_eq!(response, expected);
+        }
+
+        let audited = store.list_range("partyskunk:audit", 0, -1).await;
+        assert!(audited.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn banned_error() {
+        let mut store = mock_admin_store().await;
+        let banned = "+14105551234";
+        for (prompt, prompter, expected) in [
+            (
+                "partyskunk admin ban +14105551234",
+                "admin",
+                messages::admin_banned(banned),
+            ),
+            ("foundcode", banned, messages::banned_error()),
+            ("partyskunk valid message", banned, messages::banned_error()),
+        ] {
+            let response = handle(
+                prompt.to_string(),
+                prompter.to_string(),
+                &mock_admin_signature(),
+                &mut store,
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(response, expected)
+        }
+    }
+
+    #[tokio::test]
+    async fn maintenance_error() {
+        let mut store = mock_admin_store().await;
+        let found =
+            messages::find_success(&Config::default(), "found message".into());
+        for (prompt, prompter, expected) in [
+            (
+                "partyskunk admin maintenance on",
+                "admin",
+                messages::admin_maintenance(true),
+            ),
+            ("foundcode", "prompter", messages::maintenance_error()),
+            ("foundcode", "admin", found),
+        ] {
+            let response = handle(
+                prompt.to_string(),
+                prompter.to_string(),
+                &mock_admin_signature(),
+                &mut store,
+                &mut mock_http_client(),
+                &mut mock_classifier(),
+            )
+            .await;
+
+            assert_eq!(response, expected)
+        }
+    }
+
+    async fn mock_admin_store() -> key_value_store::InMemory {
+        let mut store = mock_key_value_store();
+        store
+            .set(
+                "partyskunk:config",
+                r#"{"admins": ["admin"]}"#,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        store.set_secret("partyskunk:twilio", MOCK_TWILIO_ACCOUNT);
+        store
+    }
+
+    const MOCK_TWILIO_ACCOUNT: &str = r#"{
+        "account_sid": "AC123",
+        "auth_token": "12345",
+        "from": "+18005551212"
+    }"#;
+
+    fn mock_admin_signature() -> twilio::Signature {
+        let account = serde_json::from_str(MOCK_TWILIO_ACCOUNT).unwrap();
+        let url = "https://example.com/";
+        twilio::Signature::signed(url, &[("From", "admin")], &account)
+    }
+
+    fn mock_signature() -> twilio::Signature {
+        twilio::Signature::default()
+    }
+
+    fn mock_key_value_store() -> key_value_store::InMemory {
+        key_value_store::InMemory::new()
+    }
+
+    fn mock_http_client() -> http_client::Tcp {
+        http_client::Tcp::new()
+    }
+
+    fn mock_classifier() -> classifier::StandIn {
+        classifier::StandIn::new(&["classified message"])
     }
 }
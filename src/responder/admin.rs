@@ -0,0 +1,128 @@
+use super::parser::AdminAction;
+use crate::{clock, key_value_store::KeyValueStore, logger};
+use serde::Serialize;
+use serde_json as json;
+use tokio::time::Duration;
+use wasmbus_rpc::actor::prelude::*;
+
+// Present while everyone but admins is being turned away.
+const MAINTENANCE_KEY: &str = "partyskunk:maintenance";
+// What admins have done, oldest first, as far back as the limit.
+const AUDIT_KEY: &str = "partyskunk:audit";
+const AUDIT_LIMIT: u32 = 1_000;
+
+#[derive(Serialize)]
+struct Audited<'a> {
+    admin: &'a str,
+    action: String,
+    // Since the unix epoch.
+    at: Duration,
+}
+
+// Called before the action is taken, so that nothing goes unrecorded.
+pub async fn audit<T: KeyValueStore>(
+    admin: &str,
+    action: &AdminAction,
+    store: &mut T,
+) -> RpcResult<()> {
+    logger::log(format!("admin: {}, action: {:?}", admin, action)).await;
+    let audited = Audited {
+        admin,
+        action: format!("{:?}", action),
+        at: clock::now(),
+    };
+    let audited = json::to_string(&audited)
+        .map_err(|error| RpcError::Ser(error.to_string()))?;
+    let audited = store.list_push(AUDIT_KEY, &audited).await?;
+    for _ in AUDIT_LIMIT..audited {
+        store.list_pop(AUDIT_KEY).await?;
+    }
+
+    Ok(())
+}
+
+fn banned_key(number: &str) -> String {
+    format!("partyskunk:banned:{}", number)
+}
+
+pub async fn is_banned<T: KeyValueStore>(
+    number: &str,
+    store: &mut T,
+) -> RpcResult<bool> {
+    Ok(store.get(&banned_key(number)).await?.is_some())
+}
+
+// Bans last until they're lifted.
+pub async fn ban<T: KeyValueStore>(
+    number: &str,
+    store: &mut T,
+) -> RpcResult<()> {
+    store.set(&banned_key(number), "", Duration::ZERO).await
+}
+
+// Tells whether the number was banned.
+pub async fn unban<T: KeyValueStore>(
+    number: &str,
+    store: &mut T,
+) -> RpcResult<bool> {
+    store.del(&banned_key(number)).await
+}
+
+pub async fn in_maintenance<T: KeyValueStore>(
+    store: &mut T,
+) -> RpcResult<bool> {
+    Ok(store.get(MAINTENANCE_KEY).await?.is_some())
+}
+
+pub async fn set_maintenance<T: KeyValueStore>(
+    on: bool,
+    store: &mut T,
+) -> RpcResult<()> {
+    if on {
+        store.set(MAINTENANCE_KEY, "", Duration::ZERO).await
+    } else {
+        store.del(MAINTENANCE_KEY).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_value_store::InMemory;
+
+    #[tokio::test]
+    async fn it_bans_and_audits() {
+        let mut store = InMemory::new();
+        let number = "+14105551234";
+        let action = AdminAction::Ban(number.to_string());
+        audit("admin", &action, &mut store).await.unwrap();
+        ban(number, &mut store).await.unwrap();
+        assert!(is_banned(number, &mut store).await.unwrap());
+        assert!(unban(number, &mut store).await.unwrap());
+        assert!(!unban(number, &mut store).await.unwrap());
+        assert!(!is_banned(number, &mut store).await.unwrap());
+
+        set_maintenance(true, &mut store).await.unwrap();
+        assert!(in_maintenance(&mut store).await.unwrap());
+        set_maintenance(false, &mut store).await.unwrap();
+        assert!(!in_maintenance(&mut store).await.unwrap());
+
+        let audited = store.list_range(AUDIT_KEY, 0, -1).await.unwrap();
+        assert_eq!(audited.len(), 1);
+        assert!(audited[0].contains(r#""admin":"admin""#));
+        assert!(audited[0].contains(r#"Ban(\"+14105551234\")"#));
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_audit_to_its_limit() {
+        let mut store = InMemory::new();
+        for i in 0..AUDIT_LIMIT + 2 {
+            let action = AdminAction::Purge(format!("code{}", i));
+            audit("admin", &action, &mut store).await.unwrap();
+        }
+
+        let audited = store.list_range(AUDIT_KEY, 0, -1).await.unwrap();
+        assert_eq!(audited.len(), AUDIT_LIMIT as usize);
+        assert!(audited[0].contains(r#"Purge(\"code2\")"#));
+    }
+}
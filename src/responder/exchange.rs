@@ -1,72 +1,801 @@
-use crate::key_value_store::KeyValueStore;
+use super::messages;
+use crate::{
+    clock,
+    config::Config,
+    http_client::HttpClient,
+    key_value_store::{self, KeyValueStore},
+    logger,
+    random::{self, Rng},
+    twilio,
+};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
 use std::result;
 use tokio::time::Duration;
 use wasmbus_rpc::actor::prelude::*;
 
 // Codes last for a day.
 const CODE_EXPIRY: Duration = Duration::from_secs(86_400);
+// How many wrong PINs a code takes before it's locked.
+const PIN_ATTEMPTS: i32 = 5;
+// At most one read receipt goes out per code in this long.
+const RECEIPT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+// How many replies a reader can pass on for each code in this long.
+const REPLY_LIMIT: i32 = 5;
+const REPLY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// How many replies a creator is shown at a time.
+const INBOX_PAGE: usize = 3;
+// How long after expiring a code is still known to have expired.
+const TOMBSTONE_EXPIRY: Duration = Duration::from_secs(86_400 * 7);
+
+// Restrictions a creator can put on who reads their message.
+#[derive(Debug, Default)]
+pub struct CreateOptions {
+    // Needed alongside the code to read the message.
+    pub pin: Option<String>,
+    // Phone numbers that can read the message, besides the creator. Anyone
+    // can when there are none.
+    pub recipients: Vec<String>,
+    // Whether to text the creator when someone reads the message.
+    pub receipts: bool,
+    // How long after creation the message can first be read.
+    pub reveal_after: Option<Duration>,
+}
 
 pub async fn create<T: KeyValueStore>(
     message: String,
+    creator: String,
+    options: CreateOptions,
+    config: &Config,
+    store: &mut T,
+) -> CreateResult {
+    check_options(&options, config)?;
+    let codebook = load_codebook(config, store).await?;
+    let scheme = Scheme {
+        codebook: &codebook,
+        words: config.code_words,
+    };
+
+    // The free list holds on to the code through its cooldown too, so that it
+    // isn't reclaimed just to be skipped over.
+    let cooldown = config.code_cooldown();
+    let mut rng = random::new().await?;
+    loop {
+        let code =
+            generate_code(scheme, CODE_EXPIRY + cooldown, &mut rng, store)
+                .await?;
+
+        // Someone may have chosen the code for themselves in the meantime.
+        let claimed =
+            claim(&code, &message, &creator, &options, config, &mut rng, store);
+        if claimed.await? {
+            return Ok(code);
+        }
+    }
+}
+
+// Creates the code the creator asked for, which has to be a word from the
+// codebook that isn't in use or cooling down.
+pub async fn create_as<T: KeyValueStore>(
+    code: String,
+    message: String,
+    creator: String,
+    options: CreateOptions,
+    config: &Config,
     store: &mut T,
 ) -> CreateResult {
-    let code = generate_code(store).await?;
-    store.set(&code, &message, CODE_EXPIRY).await?;
-    Ok(code)
+    check_options(&options, config)?;
+    let codebook = load_codebook(config, store).await?;
+    if !codebook.words.contains(&code) {
+        return Err(CreateError::NotCodeWord);
+    }
+
+    if store.get(&cooldown_key(&code)).await?.is_some() {
+        return Err(CreateError::Taken);
+    }
+
+    let mut rng = random::new().await?;
+    let claimed =
+        claim(&code, &message, &creator, &options, config, &mut rng, store);
+    if claimed.await? {
+        Ok(code)
+    } else {
+        Err(CreateError::Taken)
+    }
+}
+
+// A message without any secret to seal it with would take a code for nothing,
+// and a message held back until its code expires could never be read.
+fn check_options(
+    options: &CreateOptions,
+    config: &Config,
+) -> result::Result<(), CreateError> {
+    if config.secrets.is_empty() {
+        let error = RpcError::Other("no secrets to seal with".to_string());
+        return Err(CreateError::Unknown(error));
+    }
+
+    match options.reveal_after {
+        Some(reveal_after) if reveal_after >= CODE_EXPIRY => {
+            Err(CreateError::RevealTooLate)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Stores the message at `code` unless something already is, telling whether
+// it did.
+async fn claim<T: KeyValueStore>(
+    code: &str,
+    message: &str,
+    creator: &str,
+    options: &CreateOptions,
+    config: &Config,
+    rng: &mut Rng,
+    store: &mut T,
+) -> RpcResult<bool> {
+    let now = clock::now();
+    let mut record =
+        Record::new(message.to_string(), creator.to_string(), now, CODE_EXPIRY);
+    let pin = options.pin.as_deref();
+    if let Some(pin) = pin {
+        record.set_pin(code, pin);
+    }
+    record.recipients = options.recipients.clone();
+    record.receipts = options.receipts;
+    record.reveal_at = options.reveal_after.map(|after| now + after);
+    record.seal(&config.secrets, code, pin, rng)?;
+    if !store
+        .set_if_absent(code, &record.encode()?, CODE_EXPIRY)
+        .await?
+    {
+        return Ok(false);
+    }
+    store.set(&reads_key(code), "0", CODE_EXPIRY).await?;
+
+    // kv-store won't say when the code expires, so its tombstone is laid
+    // down now and simply outlives it.
+    store
+        .set(&tombstone_key(code), "", CODE_EXPIRY + TOMBSTONE_EXPIRY)
+        .await?;
+    let cooldown = config.code_cooldown();
+    if !cooldown.is_zero() {
+        store
+            .set(&cooldown_key(code), "", CODE_EXPIRY + cooldown)
+            .await?;
+    }
+
+    let listing = Listing {
+        code: code.to_string(),
+        expires_at: now + CODE_EXPIRY,
+    };
+    add_to_creator_index(creator, &listing, store).await?;
+
+    Ok(true)
+}
+
+// Active codes of a creator, soonest to expire first.
+pub async fn list<T: KeyValueStore>(
+    creator: String,
+    store: &mut T,
+) -> ListResult {
+    Ok(read_creator_index(&creator, store).await?)
+}
+
+pub async fn find<T: KeyValueStore, C: HttpClient>(
+    code: String,
+    pin: Option<String>,
+    reader: String,
+    config: &Config,
+    store: &mut T,
+    client: &mut C,
+) -> FindResult {
+    let pin = pin.as_deref();
+    let (code, record) = locate(code, pin, &reader, config, store).await?;
+    if let Some(reveal_at) = record.reveal_at {
+        if reveal_at > clock::now() {
+            return Err(FindError::Unrevealed(reveal_at));
+        }
+    }
+
+    // Legacy records were never given a count.
+    if record.is_legacy() {
+        logger::log(format!("legacy record at {}", code)).await;
+    } else {
+        store.incr_by(&reads_key(&code), 1).await?;
+    }
+
+    let message = record.open(&config.secrets, &code, pin)?;
+    if record.receipts {
+        if let Err(error) =
+            receipt(&code, &reader, &record, config, store, client).await
+        {
+            logger::log(format!("receipt error: {:?}", error)).await;
+        }
+    }
+
+    Ok(message)
+}
+
+// How many codes are free and in use, for anyone wondering when creating will
+// work again. Codes chosen by their creators never come off of the free list,
+// so they aren't counted.
+pub async fn status<T: KeyValueStore>(
+    config: &Config,
+    store: &mut T,
+) -> StatusResult {
+    let codebook = load_codebook(config, store).await?;
+    let scheme = Scheme {
+        codebook: &codebook,
+        words: config.code_words,
+    };
+    let expires = CODE_EXPIRY + config.code_cooldown();
+    let usage = free_list::usage(scheme, expires, store).await?;
+    Ok(Status {
+        capacity: scheme.capacity(),
+        in_use: usage.in_use,
+        next_expires_at: usage.next_expires_at,
+        codebook: codebook.name.clone(),
+        codebook_size: codebook.words.len(),
+        free_listed: usage.free_listed,
+        used_listed: usage.used_listed,
+        fresh: usage.fresh,
+    })
+}
+
+// Takes down the message at `code` for good, telling whether there was one.
+// Its tombstone and cooldown are left, so that readers are told that it
+// expired and it isn't handed out again straight away. Anything that would
+// carry over to whoever it's handed out to next goes with it.
+pub async fn purge<T: KeyValueStore>(
+    code: String,
+    store: &mut T,
+) -> PurgeResult {
+    let record = match store.get(&code).await? {
+        Some(value) => Record::decode(value)?,
+        None => return Ok(false),
+    };
+
+    store.del(&code).await?;
+    store.del(&key_value_store::claim_key(&code)).await?;
+    store.del(&reads_key(&code)).await?;
+    store.del(&pin_failures_key(&code)).await?;
+    if let Some(creator) = &record.creator {
+        remove_from_creator_index(creator, &code, store).await?;
+    }
+
+    Ok(true)
+}
+
+// Passes a reader's reply on to the creator, without either learning the
+// other's number. It's texted to them where that's set up and works, and is
+// otherwise kept for them to fetch until the code expires.
+pub async fn reply<T: KeyValueStore, C: HttpClient>(
+    code: String,
+    pin: Option<String>,
+    reply: String,
+    reader: String,
+    config: &Config,
+    store: &mut T,
+    client: &mut C,
+) -> ReplyResult {
+    let pin = pin.as_deref();
+    let (code, record) = locate(code, pin, &reader, config, store).await?;
+    let (creator, expires) = match (&record.creator, record.expires_in()) {
+        (Some(creator), Some(expires)) if !expires.is_zero() => {
+            (creator, expires)
+        }
+        // Legacy records don't know who to pass replies on to.
+        _ => return Err(FindError::Inactive),
+    };
+
+    // Each reader gets so many replies to a code, counted from the first,
+    // so that no one can flood its creator through it.
+    let key = reply_limit_key(&code, &reader);
+    if key_value_store::count(store, &key, REPLY_INTERVAL).await? > REPLY_LIMIT
+    {
+        return Err(FindError::TooManyReplies);
+    }
+
+    if let Some(account) = &config.twilio {
+        let body = messages::relayed_reply(config, &code, &reply);
+        match twilio::send(creator, &body, account, client).await {
+            Ok(()) => return Ok(Delivery::Sent),
+            Err(error) => {
+                logger::log(format!("reply error: {:?}", error)).await
+            }
+        }
+    }
+
+    // Replies are numbered from one for each code, and they and their count
+    // are all given the code's expiry.
+    let mut rng = random::new().await?;
+    let sealed = seal::seal(&config.secrets, &reply, &code, None, &mut rng)?;
+    let sealed = json::to_string(&sealed)
+        .map_err(|error| RpcError::Ser(error.to_string()))?;
+    let count =
+        key_value_store::count(store, &replies_key(&code), expires).await?;
+    store
+        .set(&reply_key(&code, count as usize), &sealed, expires)
+        .await?;
+
+    Ok(Delivery::Queued)
+}
+
+// Replies to the creator's active codes that they haven't fetched yet, oldest
+// code first, up to `INBOX_PAGE` at a time. Fetched replies are left to
+// expire along with their code.
+pub async fn inbox<T: KeyValueStore>(
+    creator: String,
+    config: &Config,
+    store: &mut T,
+) -> InboxResult {
+    let mut index = read_creator_index(&creator, store).await?;
+    index.sort_by_key(|listing| listing.expires_at);
+
+    let mut inbox = Inbox::default();
+    for listing in index {
+        let code = &listing.code;
+        let count = read_count(&replies_key(code), store).await?;
+        let fetched = read_count(&replies_fetched_key(code), store).await?;
+        let shown = count.min(fetched + INBOX_PAGE - inbox.replies.len());
+        for n in fetched + 1..=shown {
+            if let Some(sealed) = store.get(&reply_key(code, n)).await? {
+                let sealed = json::from_str(&sealed)
+                    .map_err(|error| RpcError::Deser(error.to_string()))?;
+                inbox.replies.push(Relayed {
+                    text: seal::open(&config.secrets, &sealed, code, None)?,
+                    code: code.clone(),
+                });
+            }
+        }
+
+        if shown > fetched {
+            let key = replies_fetched_key(code);
+            store
+                .set(&key, &shown.to_string(), listing.expires_in())
+                .await?;
+        }
+        inbox.remaining += count.saturating_sub(shown.max(fetched));
+    }
+
+    Ok(inbox)
+}
+
+async fn read_count<T: KeyValueStore>(
+    key: &str,
+    store: &mut T,
+) -> RpcResult<usize> {
+    let count = store.get(key).await?;
+    Ok(count.and_then(|count| count.parse().ok()).unwrap_or(0))
+}
+
+fn replies_key(code: &str) -> String {
+    format!("partyskunk:replies:{}", code)
+}
+
+fn reply_limit_key(code: &str, reader: &str) -> String {
+    format!("partyskunk:reply_limit:{}:{}", code, reader)
+}
+
+fn reply_key(code: &str, n: usize) -> String {
+    format!("partyskunk:replies:{}:{}", code, n)
+}
+
+// How many of the code's replies its creator has fetched.
+fn replies_fetched_key(code: &str) -> String {
+    format!("partyskunk:replies_fetched:{}", code)
+}
+
+// Finds the record at `code` for `reader`, correcting a misspelt code on the
+// way, and gives back the code that it was found at.
+async fn locate<T: KeyValueStore>(
+    code: String,
+    pin: Option<&str>,
+    reader: &str,
+    config: &Config,
+    store: &mut T,
+) -> result::Result<(String, Record), FindError> {
+    let found = lookup(&code, pin, reader, store).await?;
+    if let Lookup::Found(record) = found {
+        return Ok((code, *record));
+    }
+
+    let codebook = load_codebook(config, store).await?;
+    let scheme = Scheme {
+        codebook: &codebook,
+        words: config.code_words,
+    };
+    if let Lookup::Forbidden = found {
+        return Err(never_issued(&code, scheme));
+    }
+
+    // A misspelt code is only ever corrected to the one code it could have
+    // meant, going by the codebook alone. So guessing a misspelling reveals no
+    // more than guessing the code itself would, and where the codebook can't
+    // tell which was meant, suggesting codebook words reveals nothing at all.
+    match codebook.correct(&code) {
+        Correction::Corrected(code) => {
+            match lookup(&code, pin, reader, store).await? {
+                Lookup::Found(record) => Ok((code, *record)),
+                Lookup::Missing => Err(not_found(&code, scheme, store).await?),
+                Lookup::Forbidden => Err(never_issued(&code, scheme)),
+            }
+        }
+        Correction::Ambiguous(codes) => Err(FindError::Ambiguous(codes)),
+        Correction::Unchanged | Correction::Unknown => {
+            Err(not_found(&code, scheme, store).await?)
+        }
+    }
+}
+
+// Tells apart why there's nothing at `code`.
+async fn not_found<T: KeyValueStore>(
+    code: &str,
+    scheme: Scheme<'_>,
+    store: &mut T,
+) -> RpcResult<FindError> {
+    if store.get(&tombstone_key(code)).await?.is_some() {
+        Ok(FindError::Expired)
+    } else {
+        Ok(never_issued(code, scheme))
+    }
+}
+
+// What would be said of `code` had it never been handed out.
+fn never_issued(code: &str, scheme: Scheme<'_>) -> FindError {
+    if scheme.contains(code) {
+        FindError::Inactive
+    } else {
+        FindError::NotCodeWord
+    }
+}
+
+fn tombstone_key(code: &str) -> String {
+    format!("partyskunk:tombstones:{}", code)
+}
+
+// Present while a code is in use or cooling down afterwards.
+fn cooldown_key(code: &str) -> String {
+    format!("partyskunk:cooldown:{}", code)
+}
+
+enum Lookup {
+    Found(Box<Record>),
+    Missing,
+    // There's a message, but not for the reader.
+    Forbidden,
 }
 
-pub async fn find<T: KeyValueStore>(code: String, store: &mut T) -> FindResult {
-    let response = store.get(&code).await?;
-    match response {
-        Some(message) => Ok(message),
-        None => Err(FindError::NotFound),
+async fn lookup<T: KeyValueStore>(
+    code: &str,
+    pin: Option<&str>,
+    reader: &str,
+    store: &mut T,
+) -> result::Result<Lookup, FindError> {
+    let record = match store.get(code).await? {
+        Some(value) => Record::decode(value)?,
+        None => return Ok(Lookup::Missing),
+    };
+
+    // Before anything else, so that no one else learns even that there's a
+    // PIN.
+    if !record.allows_reader(reader) {
+        return Ok(Lookup::Forbidden);
     }
+
+    let expires = record.expires_in().unwrap_or_default();
+    check_pin(code, pin, &record, expires, store).await?;
+    Ok(Lookup::Found(Box::new(record)))
 }
 
+// Lets the creator know that someone read their message, without saying who.
+// A missed receipt is no reason to keep the message from its reader.
+async fn receipt<T: KeyValueStore, C: HttpClient>(
+    code: &str,
+    reader: &str,
+    record: &Record,
+    config: &Config,
+    store: &mut T,
+    client: &mut C,
+) -> RpcResult<()> {
+    let (creator, account) = match (&record.creator, &config.twilio) {
+        (Some(creator), Some(account)) if creator != reader => {
+            (creator, account)
+        }
+        _ => return Ok(()),
+    };
+
+    let key = receipt_key(code);
+    if !store.set_if_absent(&key, "", RECEIPT_INTERVAL).await? {
+        return Ok(());
+    }
+
+    let body = messages::receipt(config, code, reader, clock::now());
+    twilio::send(creator, &body, account, client).await
+}
+
+fn receipt_key(code: &str) -> String {
+    format!("partyskunk:receipts:{}", code)
+}
+
+// Wrong PINs count against the code until it expires, and once there have
+// been too many, not even the right PIN will do. Leaving the PIN out doesn't
+// count, since anyone might try a code without knowing it needs one.
+async fn check_pin<T: KeyValueStore>(
+    code: &str,
+    pin: Option<&str>,
+    record: &Record,
+    expires: Duration,
+    store: &mut T,
+) -> result::Result<(), FindError> {
+    if record.pin_hash.is_none() {
+        return Ok(());
+    }
+
+    let key = pin_failures_key(code);
+    let failures = store.get(&key).await?;
+    let failures: i32 = failures.and_then(|f| f.parse().ok()).unwrap_or(0);
+    if failures >= PIN_ATTEMPTS {
+        return Err(FindError::LockedOut);
+    }
+
+    if record.accepts_pin(code, pin) {
+        return Ok(());
+    }
+
+    if pin.is_some() {
+        // The count is given the code's expiry on the first failure.
+        key_value_store::count(store, &key, expires).await?;
+    }
+
+    Err(FindError::WrongPin)
+}
+
+fn pin_failures_key(code: &str) -> String {
+    format!("partyskunk:pin_failures:{}", code)
+}
+
+async fn load_codebook<T: KeyValueStore>(
+    config: &Config,
+    store: &mut T,
+) -> RpcResult<Codebook> {
+    // Codes starting with a create word would be read as creates instead.
+    let codebook = Codebook::load(&config.codebook, store).await?;
+    let codebook = codebook.without(config.create_words());
+    if config.exclude_confusable {
+        Ok(codebook.without_confusable())
+    } else {
+        Ok(codebook)
+    }
+}
+
+// Reads are counted apart from the record, which is never written back, so
+// that counting one can't bring back a record that's gone in the meantime.
+// The count expires along with the record.
+fn reads_key(code: &str) -> String {
+    format!("partyskunk:reads:{}", code)
+}
+
+mod analysis;
+mod codebook;
+use codebook::{Codebook, Correction};
 mod codes;
-// `CODES` shouldn't change over time in order for `partyskunk:codes_index` to
-// remain coherent with respect to it.
-use codes::CODES;
+mod free_list;
+mod record;
+use record::Record;
+mod scheme;
+use scheme::Scheme;
+mod seal;
+
+async fn generate_code<T: KeyValueStore>(
+    scheme: Scheme<'_>,
+    expires: Duration,
+    rng: &mut Rng,
+    store: &mut T,
+) -> GenerateCodeResult {
+    // Codes come off of a free list in a random order, so that no one can
+    // work out which codes were handed out just before or after their own.
+    // Expiry of the codes themselves is handled by kv-store, while the free
+    // list reclaims them once they've expired.
+    loop {
+        let index = match free_list::pop(scheme, expires, rng, store).await? {
+            Some(index) => index,
+            None => return Err(GenerateCodeError::OverCapacity),
+        };
+
+        // Codes handed out before the free list existed may still be in use,
+        // and codes held for a shorter cooldown than is configured now may
+        // still be cooling down. Having been popped they're now marked as used
+        // like any other, and will be reclaimed later.
+        let code = scheme.code(index);
+        if store.get(&code).await?.is_none()
+            && store.get(&cooldown_key(&code)).await?.is_none()
+        {
+            return Ok(code);
+        }
+    }
+}
+
+// Each creator has an index of the codes they've created, as a list that each
+// is pushed onto so that creates at the same time can't lose one another's.
+// Lists don't expire, so it is pruned of expired codes whenever it is read or
+// pushed to, which leaves at most a day's worth behind.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Listing {
+    pub code: String,
+    // Since the unix epoch.
+    pub expires_at: Duration,
+}
+
+impl Listing {
+    pub fn expires_in(&self) -> Duration {
+        self.expires_at.saturating_sub(clock::now())
+    }
+}
+
+fn creator_index_key(creator: &str) -> String {
+    format!("partyskunk:creator_codes:{}", creator)
+}
+
+async fn add_to_creator_index<T: KeyValueStore>(
+    creator: &str,
+    listing: &Listing,
+    store: &mut T,
+) -> RpcResult<()> {
+    let listing = json::to_string(listing)
+        .map_err(|error| RpcError::Ser(error.to_string()))?;
+    store
+        .list_push(&creator_index_key(creator), &listing)
+        .await?;
+    read_creator_index(creator, store).await.map(|_| ())
+}
 
-const CODES_INDEX_KEY: &str = "partyskunk:codes_index";
+// Soonest to expire first.
+async fn read_creator_index<T: KeyValueStore>(
+    creator: &str,
+    store: &mut T,
+) -> RpcResult<Vec<Listing>> {
+    let key = creator_index_key(creator);
+    let now = clock::now();
+    let mut index = Vec::new();
+    for value in store.list_range(&key, 0, -1).await? {
+        let listing: Listing = json::from_str(&value)
+            .map_err(|error| RpcError::Deser(error.to_string()))?;
+        if listing.expires_at > now {
+            index.push(listing);
+        } else {
+            store.list_del(&key, &value).await?;
+        }
+    }
 
-async fn generate_code<T: KeyValueStore>(store: &mut T) -> GenerateCodeResult {
-    // Depends on atomic increment. Our relaxed strategy (one we still need
-    // to prove is suitable enough) is as follows:
-    //
-    // 1. Increment an index into codeword list.
-    let index = store.incr_by(CODES_INDEX_KEY, 1).await?;
-    let index = (index as usize) % CODES.len();
+    index.sort_by_key(|listing| listing.expires_at);
+    Ok(index)
+}
 
-    // 2. From index, fetch candidate codeword and check if it exists.
-    // Expiry handled by kv-store.
-    let code = CODES[index];
-    let response = store.get(code).await?;
-    match response {
-        // If not, use it.
-        None => Ok(code.to_string()),
-        // Otherwise, error out with a `OverCapacity` and also decrement
-        // back.
-        Some(_) => {
-            store.incr_by(CODES_INDEX_KEY, -1).await?;
-            Err(GenerateCodeError::OverCapacity)
+async fn remove_from_creator_index<T: KeyValueStore>(
+    creator: &str,
+    code: &str,
+    store: &mut T,
+) -> RpcResult<()> {
+    let key = creator_index_key(creator);
+    for value in store.list_range(&key, 0, -1).await? {
+        let listing: Listing = json::from_str(&value)
+            .map_err(|error| RpcError::Deser(error.to_string()))?;
+        if listing.code == code {
+            store.list_del(&key, &value).await?;
         }
     }
+
+    Ok(())
 }
 
 pub type FindResult = result::Result<String, FindError>;
 pub type GenerateCodeResult = result::Result<String, GenerateCodeError>;
 pub type CreateResult = result::Result<String, CreateError>;
+pub type ListResult = result::Result<Vec<Listing>, ListError>;
+// Replies can't be passed on for the same reasons codes can't be found.
+pub type ReplyResult = result::Result<Delivery, FindError>;
+pub type InboxResult = result::Result<Inbox, InboxError>;
+pub type StatusResult = result::Result<Status, StatusError>;
+pub type PurgeResult = result::Result<bool, PurgeError>;
+
+#[derive(Debug)]
+pub struct Status {
+    pub capacity: usize,
+    pub in_use: usize,
+    // Since the unix epoch.
+    pub next_expires_at: Option<Duration>,
+    // The rest is for admins.
+    pub codebook: String,
+    // Once words that can't be used are left out.
+    pub codebook_size: usize,
+    // The lengths of the free list and used list as they are in the store.
+    pub free_listed: usize,
+    pub used_listed: usize,
+    // Never handed out, and so on neither list.
+    pub fresh: usize,
+}
+
+impl Status {
+    pub fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.in_use)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    // Texted to the creator.
+    Sent,
+    // Kept for the creator to fetch.
+    Queued,
+}
+
+#[derive(Debug, Default)]
+pub struct Inbox {
+    pub replies: Vec<Relayed>,
+    // How many more are waiting to be fetched.
+    pub remaining: usize,
+}
+
+#[derive(Debug)]
+pub struct Relayed {
+    pub code: String,
+    pub text: String,
+}
 
 #[derive(Debug)]
 pub enum CreateError {
     OverCapacity,
+    // The chosen code is in use or cooling down.
+    Taken,
+    // The chosen code isn't a word from the codebook.
+    NotCodeWord,
+    // The message would be held back until after its code expires.
+    RevealTooLate,
+    // The message was turned down by moderation.
+    Refused,
     Unknown(RpcError),
 }
 
 #[derive(Debug)]
 pub enum FindError {
-    NotFound,
+    // Was a code until recently.
+    Expired,
+    // Could be a code, but isn't one right now.
+    Inactive,
+    // Couldn't ever be a code.
+    NotCodeWord,
+    // The code needs a PIN, and wasn't given the right one.
+    WrongPin,
+    // Too many wrong PINs have been given for the code.
+    LockedOut,
+    // The message can't be read until this long after the unix epoch.
+    Unrevealed(Duration),
+    // A misspelt code that could have been any of these.
+    Ambiguous(Vec<String>),
+    // The reader has replied to the code too often lately.
+    TooManyReplies,
+    Unknown(RpcError),
+}
+
+#[derive(Debug)]
+pub enum ListError {
+    Unknown(RpcError),
+}
+
+#[derive(Debug)]
+pub enum PurgeError {
+    Unknown(RpcError),
+}
+
+#[derive(Debug)]
+pub enum StatusError {
+    Unknown(RpcError),
+}
+
+#[derive(Debug)]
+pub enum InboxError {
     Unknown(RpcError),
 }
 
@@ -97,6 +826,30 @@ impl From<RpcError> for FindError {
     }
 }
 
+impl From<RpcError> for ListError {
+    fn from(error: RpcError) -> Self {
+        ListError::Unknown(error)
+    }
+}
+
+impl From<RpcError> for PurgeError {
+    fn from(error: RpcError) -> Self {
+        PurgeError::Unknown(error)
+    }
+}
+
+impl From<RpcError> for StatusError {
+    fn from(error: RpcError) -> Self {
+        StatusError::Unknown(error)
+    }
+}
+
+impl From<RpcError> for InboxError {
+    fn from(error: RpcError) -> Self {
+        InboxError::Unknown(error)
+    }
+}
+
 impl From<RpcError> for GenerateCodeError {
     fn from(error: RpcError) -> Self {
         GenerateCodeError::Unknown(error)
@@ -106,34 +859,1468 @@ impl From<RpcError> for GenerateCodeError {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::key_value_store;
+    use crate::{http_client, key_value_store};
+    use std::collections::{HashMap, HashSet};
+
+    // Single word codes, so that the test codebook runs out after just two.
+    fn config() -> Config {
+        Config {
+            code_words: 1,
+            secrets: seal::test_secrets(&[("2", "second"), ("1", "first")]),
+            ..Config::default()
+        }
+    }
+
+    fn client() -> http_client::Tcp {
+        http_client::Tcp::new()
+    }
 
     #[tokio::test]
     async fn it_exchanges_limited_code_words_with_expiry() {
         tokio::time::pause();
         let mut store = key_value_store::InMemory::new();
+        let creator = "creator".to_string();
 
-        let result = find("hello".to_string(), &mut store).await;
-        assert!(matches!(result, Err(FindError::NotFound)));
+        let result = find(
+            "hello".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Inactive)));
 
-        let result = create("message 1".to_string(), &mut store).await;
-        assert_eq!(result.unwrap(), "hello");
+        let first = create(
+            "message 1".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        let first = first.unwrap();
+        let stored = store.get(&first).await.unwrap();
 
-        let result = find("hello".to_string(), &mut store).await;
+        let result = find(
+            first.clone(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
         assert_eq!(result.unwrap(), "message 1".to_string());
 
-        let result = create("message 2".to_string(), &mut store).await;
+        // Counting the read leaves the record as it was, and messages aren't
+        // kept in the open.
+        let record = store.get(&first).await.unwrap();
+        assert_eq!(record, stored);
+        let record = record.unwrap();
+        assert!(!record.contains("message 1"));
+        let record = Record::decode(record).unwrap();
+        assert_eq!(record.creator.unwrap(), "creator");
+        let reads = store.get(&reads_key(&first)).await.unwrap();
+        assert_eq!(reads.as_deref(), Some("1"));
+
+        let second = create(
+            "message 2".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        let second = second.unwrap();
+        assert_eq!(
+            HashSet::from([first.as_str(), second.as_str()]),
+            HashSet::from(["hello", "goodbye"])
+        );
+
+        let result = create(
+            "message 3".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::OverCapacity)));
+
+        // Expired codes cool down before being handed out again.
+        tokio::time::advance(CODE_EXPIRY).await;
+        let result = create(
+            "message 3".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::OverCapacity)));
+        let result = find(
+            first.clone(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Expired)));
+
+        tokio::time::advance(config().code_cooldown()).await;
+
+        let result = create(
+            "message 3".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        let third = result.unwrap();
+
+        let other = if third == first { second } else { first };
+        let result = find(
+            other,
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Expired)));
+
+        tokio::time::advance(CODE_EXPIRY + TOMBSTONE_EXPIRY).await;
+        tokio::time::advance(config().code_cooldown()).await;
+        let result = find(
+            "hello".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Inactive)));
+        let result = find(
+            "hallo".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Inactive)));
+        let result = find(
+            "goodbye".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Inactive)));
+        let result = find(
+            "apple".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::NotCodeWord)));
+        let result = find(
+            "hello goodbye".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::NotCodeWord)));
+    }
+
+    #[tokio::test]
+    async fn it_skips_codes_cooling_down() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let creator = "creator".to_string();
+
+        // As though "hello" had just expired.
+        let cooldown = config().code_cooldown();
+        let key = cooldown_key("hello");
+        store.set(&key, "", cooldown).await.unwrap();
+
+        let result = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
         assert_eq!(result.unwrap(), "goodbye");
 
-        let result = create("message 3".to_string(), &mut store).await;
+        let result = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
         assert!(matches!(result, Err(CreateError::OverCapacity)));
 
+        // Without any cooldown, codes are handed out again as they expire.
+        let config = Config {
+            code_cooldown_secs: 0,
+            ..config()
+        };
+        tokio::time::advance(CODE_EXPIRY + cooldown).await;
+        for _ in 0..2 {
+            let result = create(
+                "message".to_string(),
+                creator.clone(),
+                CreateOptions::default(),
+                &config,
+                &mut store,
+            )
+            .await;
+            assert!(result.is_ok());
+        }
         tokio::time::advance(CODE_EXPIRY).await;
+        let result = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_picks_codes_uniformly() {
+        let codebook = Codebook::builtin("basic").unwrap();
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 3,
+        };
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let draws = 4_000;
+        for seed in 0..draws {
+            let mut store = key_value_store::InMemory::new();
+            let mut rng = random::from_seed(seed);
+            let code =
+                generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+            *counts.entry(code.unwrap()).or_insert(0) += 1;
+        }
+
+        // Roughly seven standard deviations either side of even.
+        let even = draws as usize / scheme.capacity();
+        assert_eq!(counts.len(), scheme.capacity());
+        for count in counts.values() {
+            assert!(count.abs_diff(even) < 150, "{:?}", counts);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reclaims_expired_codes() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let mut rng = random::from_seed(0);
+        let codebook = Codebook::builtin("basic").unwrap();
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 2,
+        };
+
+        let mut codes = HashSet::new();
+        for _ in 0..scheme.capacity() {
+            let code =
+                generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+            let code = code.unwrap();
+            store.set(&code, "message", CODE_EXPIRY).await.unwrap();
+            codes.insert(code);
+            tokio::time::advance(Duration::from_secs(60)).await;
+        }
+
+        let result =
+            generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+        assert!(matches!(result, Err(GenerateCodeError::OverCapacity)));
+
+        // Only the oldest code has expired.
+        tokio::time::advance(CODE_EXPIRY - Duration::from_secs(60 * 4)).await;
+        let code =
+            generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+        assert!(codes.contains(&code.unwrap()));
+
+        let result =
+            generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+        assert!(matches!(result, Err(GenerateCodeError::OverCapacity)));
+    }
+
+    #[tokio::test]
+    async fn it_picks_codes_out_of_sequence() {
+        let mut store = key_value_store::InMemory::new();
+        let mut rng = random::from_seed(0);
+        let codebook = Codebook::builtin("basic").unwrap();
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 3,
+        };
 
-        let result = create("message 3".to_string(), &mut store).await;
+        let mut codes = Vec::new();
+        for _ in 0..scheme.capacity() {
+            let code =
+                generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+            let code = code.unwrap();
+            store.set(&code, "message", CODE_EXPIRY).await.unwrap();
+            codes.push(code);
+        }
+
+        let sequence: Vec<_> = (0..scheme.capacity())
+            .map(|index| scheme.code(index))
+            .collect();
+        assert_ne!(codes, sequence);
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), scheme.capacity());
+
+        let result =
+            generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+        assert!(matches!(result, Err(GenerateCodeError::OverCapacity)));
+    }
+
+    #[tokio::test]
+    async fn it_picks_codes_deterministically_under_a_seed() {
+        let codebook = Codebook::builtin("basic").unwrap();
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 4,
+        };
+
+        let mut sequences = Vec::new();
+        for seed in [42, 42, 43] {
+            let mut store = key_value_store::InMemory::new();
+            let mut rng = random::from_seed(seed);
+
+            let mut codes = Vec::new();
+            for _ in 0..scheme.capacity() {
+                let code =
+                    generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store)
+                        .await;
+                let code = code.unwrap();
+                store.set(&code, "message", CODE_EXPIRY).await.unwrap();
+                codes.push(code);
+            }
+
+            sequences.push(codes);
+        }
+
+        assert_eq!(sequences[0], sequences[1]);
+        assert_ne!(sequences[0], sequences[2]);
+    }
+
+    #[tokio::test]
+    async fn it_probes_past_codes_still_in_use() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let creator = "creator".to_string();
+
+        // A long lived message blocks one of the codes.
+        store
+            .set("hello", "blocking", CODE_EXPIRY * 3)
+            .await
+            .unwrap();
+
+        let result = create(
+            "message 1".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert_eq!(result.unwrap(), "goodbye");
+
+        let result = create(
+            "message 2".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::OverCapacity)));
+
+        tokio::time::advance(CODE_EXPIRY + config().code_cooldown()).await;
+
+        // The blocking code is still in use but the other one has expired.
+        let result = create(
+            "message 3".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert_eq!(result.unwrap(), "goodbye");
+
+        let result = find(
+            "hello".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "blocking".to_string());
+
+        tokio::time::advance(CODE_EXPIRY * 2).await;
+
+        let mut codes = HashSet::new();
+        for _ in 0..2 {
+            let result = create(
+                "message".to_string(),
+                creator.clone(),
+                CreateOptions::default(),
+                &config(),
+                &mut store,
+            )
+            .await;
+            codes.insert(result.unwrap());
+        }
+        assert_eq!(codes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_generates_multi_word_codes() {
+        let mut store = key_value_store::InMemory::new();
+        let mut rng = random::from_seed(0);
+        let codebook = Codebook::builtin("basic").unwrap();
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 2,
+        };
+
+        let mut codes = HashSet::new();
+        for _ in 0..scheme.capacity() {
+            let code =
+                generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+            let code = code.unwrap();
+            assert_eq!(code.split(' ').count(), 2);
+            store.set(&code, "message", CODE_EXPIRY).await.unwrap();
+            codes.insert(code);
+        }
+        assert_eq!(codes.len(), scheme.capacity());
+
+        let result =
+            generate_code(scheme, CODE_EXPIRY, &mut rng, &mut store).await;
+        assert!(matches!(result, Err(GenerateCodeError::OverCapacity)));
+    }
+
+    // The default config, but with a codebook the size of a real one and the
+    // secrets it can't do without.
+    #[tokio::test]
+    async fn it_creates_under_the_default_config() {
+        let mut store = key_value_store::InMemory::new();
+        let secrets = config().secrets;
+        let config = Config {
+            codebook: "animals".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(config.code_words, 2);
+
+        let result = create(
+            "message".to_string(),
+            "creator".to_string(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::Unknown(_))));
+
+        let config = Config { secrets, ..config };
+
+        let code = create(
+            "message".to_string(),
+            "creator".to_string(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await
+        .unwrap();
+        assert_eq!(code.split(' ').count(), 2);
+
+        let reader = "reader".to_string();
+        let result =
+            find(code, None, reader, &config, &mut store, &mut client()).await;
+        assert_eq!(result.unwrap(), "message");
+    }
+
+    #[tokio::test]
+    async fn it_leaves_out_confusable_words() {
+        let mut store = key_value_store::InMemory::new();
+        let words = r#"["pen", "pan", "boat"]"#;
+        let key = "partyskunk:codebooks:custom";
+        store.set(key, words, Duration::ZERO).await.unwrap();
+
+        let mut config = Config {
+            codebook: "custom".to_string(),
+            ..config()
+        };
+        let creator = "creator".to_string();
+
+        let mut codes = HashSet::new();
+        for _ in 0..2 {
+            let result = create(
+                "message".to_string(),
+                creator.clone(),
+                CreateOptions::default(),
+                &config,
+                &mut store,
+            )
+            .await;
+            codes.insert(result.unwrap());
+        }
+        assert_eq!(codes, HashSet::from(["pen".into(), "boat".into()]));
+
+        let result = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::OverCapacity)));
+
+        config.exclude_confusable = false;
+        let result = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await;
+        assert_eq!(result.unwrap(), "pan");
+    }
+
+    #[tokio::test]
+    async fn it_corrects_misspelt_codes() {
+        let mut store = key_value_store::InMemory::new();
+        let words = r#"["umbrella", "boat", "coat", "apple"]"#;
+        let key = "partyskunk:codebooks:custom";
+        store.set(key, words, Duration::ZERO).await.unwrap();
+        store.set("umbrella", "message", CODE_EXPIRY).await.unwrap();
+        store.set("apple boat", "pair", CODE_EXPIRY).await.unwrap();
+
+        let config = Config {
+            codebook: "custom".to_string(),
+            exclude_confusable: false,
+            ..config()
+        };
+
+        for code in ["umbrella", "umbrela", "umbrlela", "umbrellas"] {
+            let result = find(
+                code.to_string(),
+                None,
+                "reader".to_string(),
+                &config,
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert_eq!(result.unwrap(), "message", "{}", code);
+        }
+
+        let result = find(
+            "aple boat".to_string(),
+            None,
+            "reader".to_string(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "pair");
+
+        // Too far off to be a typo.
+        let result = find(
+            "umbrelaaa".to_string(),
+            None,
+            "reader".to_string(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::NotCodeWord)));
+
+        // Codebook words are never corrected, even to active codes.
+        let result = find(
+            "coat".to_string(),
+            None,
+            "reader".to_string(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Inactive)));
+
+        let result = find(
+            "moat".to_string(),
+            None,
+            "reader".to_string(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        let codes = vec!["boat".to_string(), "coat".to_string()];
+        assert!(matches!(result, Err(FindError::Ambiguous(c)) if c == codes));
+    }
+
+    async fn choose(
+        code: &str,
+        store: &mut key_value_store::InMemory,
+    ) -> CreateResult {
+        let message = "message".to_string();
+        let creator = "creator".to_string();
+        let options = CreateOptions::default();
+        create_as(
+            code.to_string(),
+            message,
+            creator,
+            options,
+            &config(),
+            store,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_creates_chosen_codes() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let creator = "creator".to_string();
+        let result = choose("cake", &mut store).await;
+        assert!(matches!(result, Err(CreateError::NotCodeWord)));
+
+        let result = choose("hello", &mut store).await;
+        assert_eq!(result.unwrap(), "hello");
+        let result = find(
+            "hello".to_string(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "message");
+        let result = choose("hello", &mut store).await;
+        assert!(matches!(result, Err(CreateError::Taken)));
+
+        // Codes handed out at random steer clear of chosen ones.
+        let result = create(
+            "random".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert_eq!(result.unwrap(), "goodbye");
+
+        // Chosen codes cool down once expired, like any other.
+        tokio::time::advance(CODE_EXPIRY).await;
+        let result = choose("hello", &mut store).await;
+        assert!(matches!(result, Err(CreateError::Taken)));
+        tokio::time::advance(config().code_cooldown()).await;
+        let result = choose("hello", &mut store).await;
         assert_eq!(result.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn it_locks_codes_after_wrong_pins() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let pin = Some("4821".to_string());
+        let code = create(
+            "secret".to_string(),
+            "creator".to_string(),
+            CreateOptions {
+                pin: pin.clone(),
+                ..CreateOptions::default()
+            },
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let result = find(
+            code.clone(),
+            pin.clone(),
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "secret");
+
+        // Leaving the PIN out doesn't count as a wrong one.
+        for _ in 0..PIN_ATTEMPTS {
+            let result = find(
+                code.clone(),
+                None,
+                "reader".to_string(),
+                &config(),
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert!(matches!(result, Err(FindError::WrongPin)));
+        }
+
+        for _ in 0..PIN_ATTEMPTS {
+            let wrong = Some("1234".to_string());
+            let result = find(
+                code.clone(),
+                wrong,
+                "reader".to_string(),
+                &config(),
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert!(matches!(result, Err(FindError::WrongPin)));
+        }
+
+        let result = find(
+            code.clone(),
+            pin.clone(),
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::LockedOut)));
+
+        // Once the code is gone, so are its wrong PINs.
+        tokio::time::advance(CODE_EXPIRY).await;
+        let result = store.get(&pin_failures_key(&code)).await.unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn it_hides_codes_from_other_readers() {
+        let mut store = key_value_store::InMemory::new();
+        let options = CreateOptions {
+            pin: Some("4821".to_string()),
+            recipients: vec!["+14105551234".to_string()],
+            ..CreateOptions::default()
+        };
+        let code = create(
+            "message".to_string(),
+            "creator".to_string(),
+            options,
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let pin = Some("4821".to_string());
+        for reader in ["+14105551234", "creator"] {
+            let reader = reader.to_string();
+            let result = find(
+                code.clone(),
+                pin.clone(),
+                reader,
+                &config(),
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert_eq!(result.unwrap(), "message");
+        }
+
+        // Others are told what they would be had it never been handed out,
+        // with or without the PIN, and their wrong PINs don't count.
+        for _ in 0..PIN_ATTEMPTS {
+            for pin in [None, Some("1234".to_string()), pin.clone()] {
+                let reader = "+14105550000".to_string();
+                let result = find(
+                    code.clone(),
+                    pin,
+                    reader,
+                    &config(),
+                    &mut store,
+                    &mut client(),
+                )
+                .await;
+                assert!(matches!(result, Err(FindError::Inactive)));
+            }
+        }
+
+        let reader = "+14105551234".to_string();
+        let result = find(
+            code.clone(),
+            pin,
+            reader,
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "message");
+        let reads = store.get(&reads_key(&code)).await.unwrap();
+        assert_eq!(reads.as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn it_sends_read_receipts_now_and_then() {
+        tokio::time::pause();
+        let stand_in = http_client::StandIn::start(201).await;
+        let mut store = key_value_store::InMemory::new();
+        let config = Config {
+            twilio: Some(twilio::Account {
+                api_url: stand_in.url.clone(),
+                account_sid: "AC123".to_string(),
+                auth_token: "token".to_string(),
+                from: "+14105550000".to_string(),
+            }),
+            ..config()
+        };
+        let options = CreateOptions {
+            receipts: true,
+            ..CreateOptions::default()
+        };
+        let creator = "+14105550001".to_string();
+        let code = create(
+            "message".to_string(),
+            creator.clone(),
+            options,
+            &config,
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let reader = "+14105559876".to_string();
+        for _ in 0..2 {
+            let result = find(
+                code.clone(),
+                None,
+                reader.clone(),
+                &config,
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert_eq!(result.unwrap(), "message");
+        }
+        let result = find(
+            code.clone(),
+            None,
+            creator.clone(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "message");
+
+        let requests = stand_in.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert!(request.starts_with(
+            "POST /2010-04-01/Accounts/AC123/Messages.json HTTP/1.1"
+        ));
+        assert!(request.contains("authorization: Basic QUMxMjM6dG9rZW4="));
+        assert!(request.contains("To=%2B14105550001&From=%2B14105550000"));
+        assert!(request.contains("********9876"));
+        assert!(!request.contains(&reader[1..]));
+
+        tokio::time::advance(RECEIPT_INTERVAL).await;
+        let result = find(
+            code.clone(),
+            None,
+            reader.clone(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "message");
+        assert_eq!(stand_in.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_reads_despite_failed_receipts() {
+        let stand_in = http_client::StandIn::start(500).await;
+        let mut store = key_value_store::InMemory::new();
+        let config = Config {
+            twilio: Some(twilio::Account {
+                api_url: stand_in.url.clone(),
+                account_sid: "AC123".to_string(),
+                auth_token: "token".to_string(),
+                from: "+14105550000".to_string(),
+            }),
+            ..config()
+        };
+        let options = CreateOptions {
+            receipts: true,
+            ..CreateOptions::default()
+        };
+        let code = create(
+            "message".to_string(),
+            "creator".to_string(),
+            options,
+            &config,
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let reader = "reader".to_string();
+        let result =
+            find(code, None, reader, &config, &mut store, &mut client()).await;
+        assert_eq!(result.unwrap(), "message");
+        assert_eq!(stand_in.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_reports_status() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let status = exchange_status(&mut store).await;
+        assert_eq!((status.free(), status.in_use), (status.capacity, 0));
+        assert_eq!(status.next_expires_at, None);
+
+        let first_expires_at =
+            clock::now() + CODE_EXPIRY + config().code_cooldown();
+        for _ in 0..2 {
+            let result = create(
+                "message".to_string(),
+                "creator".to_string(),
+                CreateOptions::default(),
+                &config(),
+                &mut store,
+            )
+            .await;
+            result.unwrap();
+            tokio::time::advance(Duration::from_secs(60)).await;
+        }
+
+        let status = exchange_status(&mut store).await;
+        assert_eq!(status.in_use, 2);
+        assert_eq!(status.free(), status.capacity - 2);
+        assert_eq!(status.next_expires_at, Some(first_expires_at));
+        assert_eq!(status.codebook, "basic");
+        assert_eq!(status.capacity, status.codebook_size);
+        assert_eq!(status.free_listed, 0);
+        assert_eq!(status.used_listed, 2);
+        assert_eq!(status.fresh, status.capacity - 2);
+
+        // Codes are back as soon as they've expired, reclaimed or not.
+        tokio::time::advance(first_expires_at - clock::now()).await;
+        let status = exchange_status(&mut store).await;
+        assert_eq!(status.in_use, 1);
+        assert_eq!(status.used_listed, 2);
+    }
+
+    async fn exchange_status(store: &mut key_value_store::InMemory) -> Status {
+        status(&config(), store).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_purges_codes() {
+        let mut store = key_value_store::InMemory::new();
+        let creator = "creator".to_string();
+        let code = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        assert!(purge(code.clone(), &mut store).await.unwrap());
+        assert!(!purge(code.clone(), &mut store).await.unwrap());
+        let result = find(
+            code.clone(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Expired)));
+        assert!(list(creator, &mut store).await.unwrap().is_empty());
+
+        // Still cooling down, so not to be handed out again just yet.
+        let result = create_as(
+            code,
+            "message".to_string(),
+            "other".to_string(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::Taken)));
+    }
+
+    #[tokio::test]
+    async fn it_claims_purged_codes_again() {
+        let mut store = key_value_store::InMemory::new();
+        let config = Config {
+            code_cooldown_secs: 0,
+            ..config()
+        };
+        let code = create(
+            "message".to_string(),
+            "creator".to_string(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await
+        .unwrap();
+        let result = find(
+            code.clone(),
+            None,
+            "reader".to_string(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        result.unwrap();
+
+        // As a store without a setnx of its own would have claimed it.
+        let claim = key_value_store::claim_key(&code);
+        store.set(&claim, "1", CODE_EXPIRY).await.unwrap();
+        assert!(purge(code.clone(), &mut store).await.unwrap());
+        assert_eq!(store.get(&claim).await.unwrap(), None);
+
+        let result = create_as(
+            code.clone(),
+            "again".to_string(),
+            "other".to_string(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await;
+        assert_eq!(result.unwrap(), code);
+        let reads = store.get(&reads_key(&code)).await.unwrap();
+        assert_eq!(reads.as_deref(), Some("0"));
+        let result = find(
+            code,
+            None,
+            "reader".to_string(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "again");
+    }
+
+    #[tokio::test]
+    async fn it_holds_messages_back_until_revealed() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let reveal_after = Duration::from_secs(2 * 3_600);
+        let options = CreateOptions {
+            reveal_after: Some(reveal_after),
+            ..CreateOptions::default()
+        };
+        let created_at = clock::now();
+        let code = create(
+            "x marks the spot".to_string(),
+            "creator".to_string(),
+            options,
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let value = store.get(&code).await.unwrap().unwrap();
+        let record = Record::decode(value).unwrap();
+        assert_eq!(record.reveal_at, Some(created_at + reveal_after));
+        assert_eq!(record.expires_at, Some(created_at + CODE_EXPIRY));
+
+        tokio::time::advance(reveal_after - Duration::from_secs(1)).await;
+        let result = find(
+            code.clone(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        let reveal_at = created_at + reveal_after;
+        assert!(
+            matches!(result, Err(FindError::Unrevealed(at)) if at == reveal_at)
+        );
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let result = find(
+            code.clone(),
+            None,
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), "x marks the spot");
+        // Only the read that got the message counts.
+        let reads = store.get(&reads_key(&code)).await.unwrap();
+        assert_eq!(reads.as_deref(), Some("1"));
+
+        // Not past the code's own expiry.
+        let options = CreateOptions {
+            reveal_after: Some(CODE_EXPIRY),
+            ..CreateOptions::default()
+        };
+        let result = create(
+            "too late".to_string(),
+            "creator".to_string(),
+            options,
+            &config(),
+            &mut store,
+        )
+        .await;
+        assert!(matches!(result, Err(CreateError::RevealTooLate)));
+    }
+
+    #[tokio::test]
+    async fn it_texts_replies_to_creators() {
+        let stand_in = http_client::StandIn::start(201).await;
+        let mut store = key_value_store::InMemory::new();
+        let config = Config {
+            twilio: Some(twilio::Account {
+                api_url: stand_in.url.clone(),
+                account_sid: "AC123".to_string(),
+                auth_token: "token".to_string(),
+                from: "+14105550000".to_string(),
+            }),
+            ..config()
+        };
+        let creator = "+14105550001".to_string();
+        let code = create(
+            "message".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config,
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let reader = "+14105559876".to_string();
+        let result = reply(
+            code.clone(),
+            None,
+            "on my way".to_string(),
+            reader.clone(),
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), Delivery::Sent);
+
+        let requests = stand_in.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert!(request.contains("To=%2B14105550001&From=%2B14105550000"));
+        assert!(request.contains("on+my+way"));
+        assert!(!request.contains(&reader[1..]));
+
+        let inbox = inbox(creator, &config, &mut store).await.unwrap();
+        assert!(inbox.replies.is_empty());
+
+        // Replies that can't be texted are kept instead.
+        let stand_in = http_client::StandIn::start(500).await;
+        let config = Config {
+            twilio: config.twilio.map(|account| twilio::Account {
+                api_url: stand_in.url.clone(),
+                ..account
+            }),
+            ..config
+        };
+        let result = reply(
+            code,
+            None,
+            "on my way".to_string(),
+            reader,
+            &config,
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), Delivery::Queued);
+    }
+
+    #[tokio::test]
+    async fn it_keeps_replies_until_codes_expire() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let creator = "creator".to_string();
+        let mut codes = Vec::new();
+        for message in ["first", "second"] {
+            let code = create(
+                message.to_string(),
+                creator.clone(),
+                CreateOptions::default(),
+                &config(),
+                &mut store,
+            )
+            .await
+            .unwrap();
+            codes.push(code);
+            tokio::time::advance(Duration::from_secs(60)).await;
+        }
+
+        // Replied to out of order, but fetched oldest code first.
+        for (code, text) in [
+            (&codes[1], "c"),
+            (&codes[0], "a"),
+            (&codes[0], "b"),
+            (&codes[1], "d"),
+        ] {
+            let result = reply(
+                code.clone(),
+                None,
+                text.to_string(),
+                "reader".to_string(),
+                &config(),
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert_eq!(result.unwrap(), Delivery::Queued);
+        }
+
+        let replied = |inbox: &Inbox| -> Vec<(String, String)> {
+            inbox
+                .replies
+                .iter()
+                .map(|relayed| (relayed.code.clone(), relayed.text.clone()))
+                .collect()
+        };
+        let first =
+            inbox(creator.clone(), &config(), &mut store).await.unwrap();
+        assert_eq!(
+            replied(&first),
+            [
+                (codes[0].clone(), "a".to_string()),
+                (codes[0].clone(), "b".to_string()),
+                (codes[1].clone(), "c".to_string()),
+            ]
+        );
+        assert_eq!(first.remaining, 1);
+        let second =
+            inbox(creator.clone(), &config(), &mut store).await.unwrap();
+        assert_eq!(replied(&second), [(codes[1].clone(), "d".to_string())]);
+        assert_eq!(second.remaining, 0);
+        let third =
+            inbox(creator.clone(), &config(), &mut store).await.unwrap();
+        assert!(third.replies.is_empty());
+
+        // Replies go with their code.
+        let result = reply(
+            codes[1].clone(),
+            None,
+            "e".to_string(),
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert_eq!(result.unwrap(), Delivery::Queued);
+        tokio::time::advance(CODE_EXPIRY).await;
+        let fourth = inbox(creator, &config(), &mut store).await.unwrap();
+        assert!(fourth.replies.is_empty());
+        assert_eq!(fourth.remaining, 0);
+        assert!(store.get(&reply_key(&codes[1], 3)).await.unwrap().is_none());
+
+        let result = reply(
+            codes[1].clone(),
+            None,
+            "f".to_string(),
+            "reader".to_string(),
+            &config(),
+            &mut store,
+            &mut client(),
+        )
+        .await;
+        assert!(matches!(result, Err(FindError::Expired)));
+    }
+
+    async fn reply_as(
+        code: &str,
+        reader: &str,
+        store: &mut key_value_store::InMemory,
+    ) -> ReplyResult {
+        let (code, text) = (code.to_string(), "hi".to_string());
+        let reader = reader.to_string();
+        reply(code, None, text, reader, &config(), store, &mut client()).await
+    }
+
+    #[tokio::test]
+    async fn it_limits_replies_from_each_reader() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let code = create(
+            "message".to_string(),
+            "creator".to_string(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        for _ in 0..REPLY_LIMIT {
+            assert_eq!(
+                reply_as(&code, "reader", &mut store).await.unwrap(),
+                Delivery::Queued
+            );
+        }
+        let result = reply_as(&code, "reader", &mut store).await;
+        assert!(matches!(result, Err(FindError::TooManyReplies)));
+
+        // Other readers have their own limit, and it's lifted in time.
+        assert_eq!(
+            reply_as(&code, "other", &mut store).await.unwrap(),
+            Delivery::Queued
+        );
+        tokio::time::advance(REPLY_INTERVAL).await;
+        assert_eq!(
+            reply_as(&code, "reader", &mut store).await.unwrap(),
+            Delivery::Queued
+        );
+    }
+
+    #[tokio::test]
+    async fn it_finds_legacy_messages() {
+        let mut store = key_value_store::InMemory::new();
+        store.set("hello", "legacy", CODE_EXPIRY).await.unwrap();
+
+        for _ in 0..2 {
+            let result = find(
+                "hello".to_string(),
+                None,
+                "reader".to_string(),
+                &config(),
+                &mut store,
+                &mut client(),
+            )
+            .await;
+            assert_eq!(result.unwrap(), "legacy".to_string());
+        }
+
+        let result = store.get("hello").await.unwrap();
+        assert_eq!(result.unwrap(), "legacy".to_string());
+        let reads = store.get(&reads_key("hello")).await.unwrap();
+        assert_eq!(reads, None);
+    }
+
+    #[tokio::test]
+    async fn it_lists_active_codes_by_creator() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+
+        let result = list("creator".to_string(), &mut store).await;
+        assert!(result.unwrap().is_empty());
+
+        let creator = "creator".to_string();
+        let first = create(
+            "message 1".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+        tokio::time::advance(Duration::from_secs(60)).await;
+        let second = create(
+            "message 2".to_string(),
+            "other".to_string(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        let result = list(creator.clone(), &mut store).await.unwrap();
+        let codes: Vec<_> = result.iter().map(|l| l.code.clone()).collect();
+        assert_eq!(codes, vec![first]);
+        assert_eq!(
+            result[0].expires_in(),
+            CODE_EXPIRY - Duration::from_secs(60)
+        );
+
+        let result = list("other".to_string(), &mut store).await.unwrap();
+        let codes: Vec<_> = result.iter().map(|l| l.code.clone()).collect();
+        assert_eq!(codes, vec![second]);
+
+        let cooldown = config().code_cooldown();
+        let elapsed = CODE_EXPIRY - Duration::from_secs(60) + cooldown;
+        tokio::time::advance(elapsed).await;
+
+        create(
+            "message 3".to_string(),
+            creator.clone(),
+            CreateOptions::default(),
+            &config(),
+            &mut store,
+        )
+        .await
+        .unwrap();
+
+        // Creating prunes expired codes, even without listing them.
+        let key = creator_index_key(&creator);
+        let index = store.list_range(&key, 0, -1).await.unwrap();
+        assert_eq!(index.len(), 1);
 
-        let result = find("goodbye".to_string(), &mut store).await;
-        assert!(matches!(result, Err(FindError::NotFound)));
+        let result = list(creator, &mut store).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].expires_in(), CODE_EXPIRY);
     }
 }
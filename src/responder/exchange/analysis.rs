@@ -0,0 +1,283 @@
+// Finds code words that are too easily mistaken for one another, whether read
+// aloud or texted. Two words are confusable when they sound alike, going by
+// their metaphone keys, or are a single typo apart. `build.rs` includes this
+// too, to report on the built in codebooks.
+use std::collections::{BTreeMap, HashSet};
+
+// At most this many edits apart counts as a typo.
+pub const TYPO_DISTANCE: usize = 1;
+
+pub fn confusable(a: &str, b: &str) -> bool {
+    metaphone(a) == metaphone(b) || edit_distance(a, b) <= TYPO_DISTANCE
+}
+
+// Every confusable pair, each in the order that the words are.
+pub fn conflicts(words: &[String]) -> Vec<(String, String)> {
+    let mut conflicts = Vec::new();
+    for (i, a) in words.iter().enumerate() {
+        for b in &words[i + 1..] {
+            if confusable(a, b) {
+                conflicts.push((a.clone(), b.clone()));
+            }
+        }
+    }
+
+    conflicts
+}
+
+// Words to leave out so that no two remaining words conflict. Greedily drops
+// whichever word conflicts with the most others, preferring to keep earlier
+// words on ties, which keeps as many words as is easily done.
+pub fn exclusions(words: &[String]) -> HashSet<String> {
+    let mut conflicts = conflicts(words);
+    let mut excluded = HashSet::new();
+
+    while !conflicts.is_empty() {
+        let mut degrees: BTreeMap<usize, usize> = BTreeMap::new();
+        for (a, b) in &conflicts {
+            for word in [a, b] {
+                let position = words.iter().position(|w| w == word).unwrap();
+                *degrees.entry(position).or_default() += 1;
+            }
+        }
+
+        let (position, _) = degrees
+            .into_iter()
+            .max_by_key(|(position, degree)| (*degree, *position))
+            .unwrap();
+        let word = &words[position];
+        conflicts.retain(|(a, b)| a != word && b != word);
+        excluded.insert(word.clone());
+    }
+
+    excluded
+}
+
+// Levenshtein distance, where swapping two neighbouring letters also counts as
+// a single edit since that's the most common typo.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    rows[0] = (0..=b.len()).collect();
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+
+            rows[i][j] = distance;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+// Lawrence Philips' original metaphone, which keys words by how they sound.
+// Vowels only count at the start of a word, so words differing by a vowel
+// sound alike, as they tend to over the phone.
+pub fn metaphone(word: &str) -> String {
+    let mut word: Vec<char> = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    // Silent or odd beginnings.
+    match word.as_slice() {
+        ['A', 'E', ..]
+        | ['G', 'N', ..]
+        | ['K', 'N', ..]
+        | ['P', 'N', ..]
+        | ['W', 'R', ..] => {
+            word.remove(0);
+        }
+        ['X', ..] => word[0] = 'S',
+        ['W', 'H', ..] => {
+            word.remove(1);
+        }
+        _ => {}
+    }
+
+    let at = |i: isize| -> char {
+        if i < 0 {
+            '\0'
+        } else {
+            word.get(i as usize).copied().unwrap_or('\0')
+        }
+    };
+    let is_vowel = |c: char| "AEIOU".contains(c);
+    let is_front = |c: char| "EIY".contains(c);
+
+    let mut key = String::new();
+    for i in 0..word.len() as isize {
+        let (prev, c, next) = (at(i - 1), at(i), at(i + 1));
+
+        // Doubled letters sound once, except for "cc" as in "accent".
+        if c == prev && c != 'C' {
+            continue;
+        }
+
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => {
+                if i == 0 {
+                    key.push(c);
+                }
+            }
+            'B' => {
+                if !(prev == 'M' && next == '\0') {
+                    key.push('B');
+                }
+            }
+            'C' => {
+                if next == 'I' && at(i + 2) == 'A' || next == 'H' {
+                    key.push(if prev == 'S' { 'K' } else { 'X' });
+                } else if is_front(next) {
+                    if prev != 'S' {
+                        key.push('S');
+                    }
+                } else {
+                    key.push('K');
+                }
+            }
+            'D' => {
+                if next == 'G' && is_front(at(i + 2)) {
+                    key.push('J');
+                } else {
+                    key.push('T');
+                }
+            }
+            'G' => {
+                let silent = next == 'H' && !is_vowel(at(i + 2))
+                    || next == 'N' && at(i + 2) == '\0'
+                    || next == 'N'
+                        && at(i + 2) == 'E'
+                        && at(i + 3) == 'D'
+                        && at(i + 4) == '\0';
+                if silent {
+                    continue;
+                }
+                if is_front(next) && prev != 'G' {
+                    key.push('J');
+                } else {
+                    key.push('K');
+                }
+            }
+            'H' => {
+                let after_modifier = "CSPTG".contains(prev);
+                if !(after_modifier || is_vowel(prev) && !is_vowel(next)) {
+                    key.push('H');
+                }
+            }
+            'K' => {
+                if prev != 'C' {
+                    key.push('K');
+                }
+            }
+            'P' => key.push(if next == 'H' { 'F' } else { 'P' }),
+            'Q' => key.push('K'),
+            'S' => {
+                if next == 'H'
+                    || next == 'I' && (at(i + 2) == 'O' || at(i + 2) == 'A')
+                {
+                    key.push('X');
+                } else {
+                    key.push('S');
+                }
+            }
+            'T' => {
+                if next == 'I' && (at(i + 2) == 'O' || at(i + 2) == 'A') {
+                    key.push('X');
+                } else if next == 'H' {
+                    key.push('0');
+                } else if !(next == 'C' && at(i + 2) == 'H') {
+                    key.push('T');
+                }
+            }
+            'V' => key.push('F'),
+            'W' | 'Y' => {
+                if is_vowel(next) {
+                    key.push(c);
+                }
+            }
+            'X' => key.push_str("KS"),
+            'Z' => key.push('S'),
+            _ => key.push(c),
+        }
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn it_keys_words_by_sound() {
+        for (word, key) in [
+            ("pen", "PN"),
+            ("pin", "PN"),
+            ("rod", "RT"),
+            ("rat", "RT"),
+            ("sun", "SN"),
+            ("son", "SN"),
+            ("knife", "NF"),
+            ("phone", "FN"),
+            ("thumb", "0M"),
+            ("church", "XRX"),
+            ("science", "SNS"),
+            ("apple", "APL"),
+            ("whistle", "WSTL"),
+            ("knight", "NT"),
+            ("box", "BKS"),
+        ] {
+            assert_eq!(metaphone(word), key, "{}", word);
+        }
+    }
+
+    #[test]
+    fn it_measures_edit_distance() {
+        assert_eq!(edit_distance("umbrela", "umbrella"), 1);
+        assert_eq!(edit_distance("umbrlela", "umbrella"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "boat"), 4);
+        assert_eq!(edit_distance("boat", "boat"), 0);
+    }
+
+    #[test]
+    fn it_finds_confusable_pairs() {
+        assert!(confusable("pen", "pin"));
+        assert!(confusable("rod", "rat"));
+        assert!(confusable("bag", "bat"));
+        assert!(!confusable("apple", "boat"));
+
+        let words = words(&["pen", "pin", "pan", "boat", "rod", "rat"]);
+        let excluded = exclusions(&words);
+        assert_eq!(
+            excluded,
+            HashSet::from(["pan".into(), "pin".into(), "rat".into()])
+        );
+
+        let remaining: Vec<_> = words
+            .iter()
+            .filter(|word| !excluded.contains(*word))
+            .cloned()
+            .collect();
+        assert!(conflicts(&remaining).is_empty());
+    }
+}
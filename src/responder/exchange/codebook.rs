@@ -0,0 +1,310 @@
+use super::{analysis, codes};
+use crate::{key_value_store::KeyValueStore, responder::parser};
+use regex::Regex;
+use serde_json as json;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+use wasmbus_rpc::actor::prelude::*;
+
+// The words that codes are made of. Besides the built in ones, a deployment
+// can keep its own in the kv-store as a JSON list of words.
+#[derive(Debug)]
+pub struct Codebook {
+    pub name: String,
+    pub words: Vec<String>,
+}
+
+impl Codebook {
+    pub fn builtin(name: &str) -> Option<Codebook> {
+        let words = match name {
+            "basic" => codes::CODES,
+            name => codes::CODEBOOKS
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, words)| *words)?,
+        };
+
+        Some(Codebook {
+            name: name.to_string(),
+            words: words.iter().map(|word| word.to_string()).collect(),
+        })
+    }
+
+    pub async fn load<T: KeyValueStore>(
+        name: &str,
+        store: &mut T,
+    ) -> RpcResult<Codebook> {
+        let codebook = match Codebook::builtin(name) {
+            Some(codebook) => codebook,
+            None => {
+                let key = format!("partyskunk:codebooks:{}", name);
+                let words = store.get(&key).await?.ok_or_else(|| {
+                    RpcError::Other(format!("codebook not found: {}", name))
+                })?;
+                let words = json::from_str(&words)
+                    .map_err(|error| RpcError::Deser(error.to_string()))?;
+
+                Codebook {
+                    name: name.to_string(),
+                    words,
+                }
+            }
+        };
+
+        codebook.validate().map_err(|error| {
+            RpcError::Other(format!("codebook {} invalid: {:?}", name, error))
+        })?;
+        Ok(codebook)
+    }
+
+    // Every word has to be something `parser::parse` reads as a code, and
+    // can't be mistaken for a keyword. Codes are looked up by their words, so
+    // words can't repeat either.
+    pub fn validate(&self) -> Result<(), CodebookError> {
+        if self.words.is_empty() {
+            return Err(CodebookError::Empty);
+        }
+
+        let regex = Regex::new(r"^[a-z]+$").unwrap();
+        let mut seen = HashSet::new();
+        for word in &self.words {
+            if !regex.is_match(word) {
+                return Err(CodebookError::Malformed(word.clone()));
+            }
+            if parser::RESERVED_WORDS.contains(&word.as_str()) {
+                return Err(CodebookError::Reserved(word.clone()));
+            }
+            if !seen.insert(word) {
+                return Err(CodebookError::Duplicate(word.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Leaves out words that are easily mistaken for others, whether read
+    // aloud or texted.
+    pub fn without_confusable(self) -> Codebook {
+        let excluded = exclusions(&self.words);
+        Codebook {
+            name: self.name,
+            words: self
+                .words
+                .into_iter()
+                .filter(|word| !excluded.contains(word))
+                .collect(),
+        }
+    }
+
+    // Leaves out `words`, whatever their case.
+    pub fn without<'a>(self, words: impl Iterator<Item = &'a str>) -> Codebook {
+        let words: Vec<_> = words.map(str::to_lowercase).collect();
+        Codebook {
+            name: self.name,
+            words: self
+                .words
+                .into_iter()
+                .filter(|word| !words.contains(word))
+                .collect(),
+        }
+    }
+
+    // Works out which code a possibly misspelt `code` was meant to be, word by
+    // word, from the words of the codebook alone.
+    pub fn correct(&self, code: &str) -> Correction {
+        let mut candidates: Vec<Vec<&str>> = vec![Vec::new()];
+        for word in code.split(' ') {
+            let corrections: Vec<_> = if self.words.iter().any(|w| w == word) {
+                vec![word]
+            } else {
+                self.words
+                    .iter()
+                    .filter(|w| {
+                        analysis::edit_distance(w, word)
+                            <= analysis::TYPO_DISTANCE
+                    })
+                    .map(String::as_str)
+                    .collect()
+            };
+
+            candidates = candidates
+                .into_iter()
+                .flat_map(|candidate| {
+                    corrections.iter().map(move |correction| {
+                        let mut candidate = candidate.clone();
+                        candidate.push(correction);
+                        candidate
+                    })
+                })
+                .take(SUGGESTION_LIMIT)
+                .collect();
+        }
+
+        let mut candidates: Vec<_> = candidates
+            .into_iter()
+            .map(|words| words.join(" "))
+            .collect();
+        match candidates.len() {
+            0 => Correction::Unknown,
+            1 if candidates[0] == code => Correction::Unchanged,
+            1 => Correction::Corrected(candidates.remove(0)),
+            _ => Correction::Ambiguous(candidates),
+        }
+    }
+
+    // Changes whenever the words do, so that anything kept per codebook starts
+    // over instead of mixing up indexes into different lists of words. This is
+    // FNV-1a, being stable across builds where `std`'s hashers aren't.
+    pub fn fingerprint(&self) -> String {
+        let mut hash: u32 = 0x811c9dc5;
+        for word in &self.words {
+            for byte in word.bytes().chain([b'\n']) {
+                hash ^= byte as u32;
+                hash = hash.wrapping_mul(0x01000193);
+            }
+        }
+
+        format!("{:08x}", hash)
+    }
+}
+
+thread_local! {
+    // Working out exclusions compares every pair of words, so it's only done
+    // once for each list of words for as long as the actor runs.
+    static EXCLUSIONS: RefCell<HashMap<Vec<String>, Rc<HashSet<String>>>> =
+        RefCell::new(HashMap::new());
+}
+
+fn exclusions(words: &[String]) -> Rc<HashSet<String>> {
+    EXCLUSIONS.with(|cache| {
+        cache
+            .borrow_mut()
+            .entry(words.to_vec())
+            .or_insert_with(|| Rc::new(analysis::exclusions(words)))
+            .clone()
+    })
+}
+
+// Suggesting more than a few codes wouldn't help anyone.
+const SUGGESTION_LIMIT: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum Correction {
+    Unchanged,
+    Corrected(String),
+    Ambiguous(Vec<String>),
+    // Not close to any code.
+    Unknown,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CodebookError {
+    Empty,
+    Malformed(String),
+    Reserved(String),
+    Duplicate(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_value_store;
+    use tokio::time::Duration;
+
+    fn codebook(words: &[&str]) -> Codebook {
+        Codebook {
+            name: "test".to_string(),
+            words: words.iter().map(|word| word.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn builtins_are_valid() {
+        for (name, _) in codes::CODEBOOKS {
+            let codebook = Codebook::builtin(name).unwrap();
+            assert_eq!(codebook.validate(), Ok(()), "{}", name);
+        }
+
+        for name in ["basic", "animals", "colours", "food", "kids"] {
+            assert!(Codebook::builtin(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn it_corrects_codes() {
+        let codebook = codebook(&["umbrella", "boat", "coat", "apple"]);
+        let correct = |code: &str| codebook.correct(code);
+
+        assert_eq!(correct("boat"), Correction::Unchanged);
+        assert_eq!(correct("apple boat"), Correction::Unchanged);
+        let corrected = Correction::Corrected("umbrella".to_string());
+        assert_eq!(correct("umbrela"), corrected);
+        let corrected = Correction::Corrected("apple boat".to_string());
+        assert_eq!(correct("appel boat"), corrected);
+
+        let ambiguous = vec!["boat".to_string(), "coat".to_string()];
+        assert_eq!(correct("moat"), Correction::Ambiguous(ambiguous));
+        let ambiguous = vec!["boat boat", "boat coat", "coat boat"];
+        let ambiguous = ambiguous.into_iter().map(String::from).collect();
+        assert_eq!(correct("moat moat"), Correction::Ambiguous(ambiguous));
+
+        assert_eq!(correct("umbrelaaa"), Correction::Unknown);
+        assert_eq!(correct("apple umbrelaaa"), Correction::Unknown);
+    }
+
+    #[test]
+    fn it_leaves_out_words() {
+        let codebook = codebook(&["apple", "new", "boat", "save"]);
+        let codebook = codebook.without(["SAVE", "new"].into_iter());
+        assert_eq!(codebook.words, vec!["apple", "boat"]);
+    }
+
+    #[test]
+    fn fingerprints_follow_words() {
+        let fingerprint = codebook(&["apple", "boat"]).fingerprint();
+        assert_eq!(fingerprint, codebook(&["apple", "boat"]).fingerprint());
+        assert_ne!(fingerprint, codebook(&["boat", "apple"]).fingerprint());
+        assert_ne!(fingerprint, codebook(&["apple", "bo", "at"]).fingerprint());
+    }
+
+    #[test]
+    fn it_validates_words() {
+        let error = codebook(&[]).validate();
+        assert_eq!(error, Err(CodebookError::Empty));
+
+        let error = codebook(&["apple", "Boat"]).validate();
+        assert_eq!(error, Err(CodebookError::Malformed("Boat".to_string())));
+
+        let error = codebook(&["apple", "partyskunk"]).validate();
+        let reserved = CodebookError::Reserved("partyskunk".to_string());
+        assert_eq!(error, Err(reserved));
+
+        let error = codebook(&["apple", "boat", "apple"]).validate();
+        assert_eq!(error, Err(CodebookError::Duplicate("apple".to_string())));
+    }
+
+    #[tokio::test]
+    async fn it_loads_codebooks_from_the_store() {
+        let mut store = key_value_store::InMemory::new();
+
+        let codebook = Codebook::load("animals", &mut store).await.unwrap();
+        assert!(codebook.words.contains(&"zebra".to_string()));
+
+        let result = Codebook::load("custom", &mut store).await;
+        assert!(result.is_err());
+
+        let key = "partyskunk:codebooks:custom";
+        let words = r#"["red", "green"]"#;
+        store.set(key, words, Duration::ZERO).await.unwrap();
+        let codebook = Codebook::load("custom", &mut store).await.unwrap();
+        assert_eq!(codebook.words, vec!["red", "green"]);
+
+        let words = r#"["red", "red"]"#;
+        store.set(key, words, Duration::ZERO).await.unwrap();
+        let result = Codebook::load("custom", &mut store).await;
+        assert!(result.is_err());
+    }
+}
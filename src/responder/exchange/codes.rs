@@ -1,40 +1,11 @@
 #![allow(clippy::all)]
 
+// The built in codebooks, generated by `build.rs` from the word lists in
+// `codebooks/words`.
+include!(concat!(env!("OUT_DIR"), "/codes.rs"));
+
 #[cfg(test)]
 pub const CODES: &[&str] = &["hello", "goodbye"];
 
-// Taken from https://en.wiktionary.org/wiki/Appendix:Basic_English_word_list
-// "Things - 200 picturable words"
 #[cfg(not(test))]
-pub const CODES: &[&str] = &
-// SEARCH_SCRIPT_BOUNDARY
-[
-    "angle", "ant", "apple", "arch", "arm", "army", "baby", "bag", "ball",
-    "band", "basin", "basket", "bath", "bed", "bee", "bell", "berry", "bird",
-    "blade", "board", "boat", "bone", "book", "boot", "bottle", "box", "boy",
-    "brain", "brake", "branch", "brick", "bridge", "brush", "bucket", "bulb",
-    "button", "cake", "camera", "card", "cart", "carriage", "cat", "chain",
-    "cheese", "chest", "chin", "church", "circle", "clock", "cloud", "coat",
-    "collar", "comb", "cord", "cow", "cup", "curtain", "cushion", "dog",
-    "door", "drain", "drawer", "dress", "drop", "ear", "egg", "engine", "eye",
-    "face", "farm", "feather", "finger", "fish", "flag", "floor", "fly",
-    "foot", "fork", "fowl", "frame", "garden", "girl", "glove", "goat", "gun",
-    "hair", "hammer", "hand", "hat", "head", "heart", "hook", "horn", "horse",
-    "hospital", "house", "island", "jewel", "kettle", "key", "knee", "knife",
-    "knot", "leaf", "leg", "library", "line", "lip", "lock", "map", "match",
-    "monkey", "moon", "mouth", "muscle", "nail", "neck", "needle", "nerve",
-    "net", "nose", "nut", "office", "orange", "oven", "parcel", "pen",
-    "pencil", "picture", "pig", "pin", "pipe", "plane", "plate", "plough",
-    "pocket", "pot", "potato", "prison", "pump", "rail", "rat", "receipt",
-    "ring", "rod", "roof", "root", "sail", "school", "scissors", "screw",
-    "seed", "sheep", "shelf", "ship", "shirt", "shoe", "skin", "skirt",
-    "snake", "sock", "spade", "sponge", "spoon", "spring", "square", "stamp",
-    "star", "station", "stem", "stick", "stocking", "stomach", "store",
-    "street", "sun", "table", "tail", "thread", "throat", "thumb", "ticket",
-    "toe", "tongue", "tooth", "town", "train", "tray", "tree", "trousers",
-    "umbrella", "wall", "watch", "wheel", "whip", "whistle", "window", "wing",
-    "wire", "worm",
-]
-// SEARCH_SCRIPT_BOUNDARY
-// ---------------------- used by `./phone_number_search/run.sh`
-;
+pub const CODES: &[&str] = BASIC;
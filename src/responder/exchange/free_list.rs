@@ -0,0 +1,479 @@
+use super::scheme::Scheme;
+use crate::{clock, key_value_store::KeyValueStore, random::Rng};
+use rand::{seq::SliceRandom, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use sha2::{Digest, Sha256};
+use tokio::time::Duration;
+use wasmbus_rpc::actor::prelude::*;
+
+// Indexes into a scheme's codes that are free to be handed out again, put back
+// in a random order once their codes expire.
+const FREE_KEY: &str = "partyskunk:free_codes";
+// Indexes that have never been handed out don't need a list. They come out in
+// an order of their own under this seed, as far along as this cursor.
+const SEED_KEY: &str = "partyskunk:free_codes_seed";
+const CURSOR_KEY: &str = "partyskunk:free_codes_cursor";
+// Indexes that have been handed out, in the order that they were.
+const USED_KEY: &str = "partyskunk:used_codes";
+
+// How much of the used list to look at per request while reclaiming.
+const RECLAIM_BATCH: i32 = 16;
+// Enough for the order to look random to anyone without the seed.
+const FEISTEL_ROUNDS: u8 = 4;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Used {
+    index: usize,
+    // Both since the unix epoch. Not every index is held equally long, so the
+    // used list is only in order of when they were handed out.
+    handed_at: Duration,
+    expires_at: Duration,
+}
+
+// Pops a free index, marking it used until `expires` from now. Only comes up
+// empty when every index is in use.
+pub async fn pop<T: KeyValueStore>(
+    scheme: Scheme<'_>,
+    expires: Duration,
+    rng: &mut Rng,
+    store: &mut T,
+) -> RpcResult<Option<usize>> {
+    let free_key = scheme.key(FREE_KEY);
+    let index = match store.list_pop(&free_key).await? {
+        Some(index) => Some(parse(&index)?),
+        None => fresh(scheme, rng, store).await?,
+    };
+
+    let index = match index {
+        Some(index) => index,
+        None => {
+            if reclaim(scheme, expires, rng, store).await? == 0 {
+                return Ok(None);
+            }
+
+            match store.list_pop(&free_key).await? {
+                Some(index) => parse(&index)?,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let now = clock::now();
+    let used = Used {
+        index,
+        handed_at: now,
+        expires_at: now + expires,
+    };
+    let used = json::to_string(&used)
+        .map_err(|error| RpcError::Ser(error.to_string()))?;
+    store.list_push(&scheme.key(USED_KEY), &used).await?;
+
+    Ok(Some(index))
+}
+
+fn parse(index: &str) -> RpcResult<usize> {
+    index
+        .parse()
+        .map_err(|_| RpcError::Deser(format!("free index: {}", index)))
+}
+
+// Hands out each index that's never been handed out before, once. The cursor
+// only ever counts up, so concurrent callers each get their own, and there's
+// no list to fill up front.
+async fn fresh<T: KeyValueStore>(
+    scheme: Scheme<'_>,
+    rng: &mut Rng,
+    store: &mut T,
+) -> RpcResult<Option<usize>> {
+    let capacity = scheme.capacity();
+    let cursor_key = scheme.key(CURSOR_KEY);
+    // So that the cursor stops counting once they've all been handed out.
+    let cursor = store.get(&cursor_key).await?;
+    let cursor = cursor.and_then(|cursor| cursor.parse().ok()).unwrap_or(0);
+    if cursor >= capacity {
+        return Ok(None);
+    }
+
+    let cursor = store.incr_by(&cursor_key, 1).await? as usize - 1;
+    if cursor >= capacity {
+        return Ok(None);
+    }
+
+    let seed = seed(scheme, rng, store).await?;
+    Ok(Some(permute(cursor, capacity, seed)))
+}
+
+// Chosen by whoever hands out a scheme's first index. Everyone pushes theirs
+// if there's none yet, but only the first one pushed is ever used.
+async fn seed<T: KeyValueStore>(
+    scheme: Scheme<'_>,
+    rng: &mut Rng,
+    store: &mut T,
+) -> RpcResult<u64> {
+    let seed_key = scheme.key(SEED_KEY);
+    let mut seeds = store.list_range(&seed_key, 0, 0).await?;
+    if seeds.is_empty() {
+        store
+            .list_push(&seed_key, &rng.next_u64().to_string())
+            .await?;
+        seeds = store.list_range(&seed_key, 0, 0).await?;
+    }
+
+    match seeds.first() {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| RpcError::Deser(format!("seed: {}", seed))),
+        None => Err(RpcError::Other(format!("no seed at {}", seed_key))),
+    }
+}
+
+// A shuffle of `0..n` under `seed`, without ever holding the whole of it: a
+// Feistel network over the smallest power of four at least `n`, applied again
+// until it lands back inside. That's under four times on average.
+fn permute(index: usize, n: usize, seed: u64) -> usize {
+    let mut half_bits = 1;
+    while 1u64 << (2 * half_bits) < n as u64 {
+        half_bits += 1;
+    }
+    let mask = (1u64 << half_bits) - 1;
+
+    let mut value = index as u64;
+    loop {
+        let (mut left, mut right) = (value >> half_bits, value & mask);
+        for round in 0..FEISTEL_ROUNDS {
+            let hash = Sha256::digest(format!("{}:{}:{}", seed, round, right));
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&hash[..8]);
+            let mixed = u64::from_le_bytes(bytes) & mask;
+            (left, right) = (right, left ^ mixed);
+        }
+
+        value = (left << half_bits) | right;
+        if value < n as u64 {
+            return value as usize;
+        }
+    }
+}
+
+// How a scheme's indexes stand, worked out without going through either list
+// whole.
+#[derive(Debug)]
+pub struct Usage {
+    // Handed out and not yet expired, cooldown and all.
+    pub in_use: usize,
+    // When the soonest of those expires, since the unix epoch.
+    pub next_expires_at: Option<Duration>,
+    // Entries in the used list, including expired ones not yet reclaimed.
+    pub used_listed: usize,
+    pub free_listed: usize,
+    // Never handed out, and so on neither list.
+    pub fresh: usize,
+}
+
+// Going by how long `expires` holds an index now, like reclaiming does. Those
+// handed out since then are all still in use, and the soonest of them to
+// expire is the first that isn't held for longer. Those handed out before are
+// at the head of the used list, which reclaiming clears out, and are looked at
+// one by one for any that are held for longer. Any held for shorter, from
+// before the cooldown was lengthened, are counted as in use until then.
+pub async fn usage<T: KeyValueStore>(
+    scheme: Scheme<'_>,
+    expires: Duration,
+    store: &mut T,
+) -> RpcResult<Usage> {
+    let now = clock::now();
+    let used_key = scheme.key(USED_KEY);
+    let used_listed = partition_point(&used_key, |_| Ok(true), store).await?;
+    let stale = |entry: &str| Ok(decode(entry)?.handed_at + expires <= now);
+    let stale = partition_point(&used_key, stale, store).await?;
+    let mut in_use = used_listed - stale;
+    let mut next_expires_at = None;
+
+    let mut start = 0;
+    'counting: loop {
+        let end = start + RECLAIM_BATCH as usize - 1;
+        let batch = store.list_range(&used_key, start as i32, end as i32);
+        let batch = batch.await?;
+        if batch.is_empty() {
+            break;
+        }
+
+        for entry in batch {
+            let used = decode(&entry)?;
+            let is_stale = start < stale;
+            start += 1;
+            if used.expires_at <= now {
+                continue;
+            }
+
+            next_expires_at = Some(match next_expires_at {
+                Some(next) => used.expires_at.min(next),
+                None => used.expires_at,
+            });
+            if is_stale {
+                in_use += 1;
+            } else if used.expires_at <= used.handed_at + expires {
+                break 'counting;
+            }
+        }
+    }
+
+    let capacity = scheme.capacity();
+    let cursor = store.get(&scheme.key(CURSOR_KEY)).await?;
+    let cursor = cursor.and_then(|cursor| cursor.parse().ok()).unwrap_or(0);
+    let free_key = scheme.key(FREE_KEY);
+    Ok(Usage {
+        in_use,
+        next_expires_at,
+        used_listed,
+        free_listed: partition_point(&free_key, |_| Ok(true), store).await?,
+        fresh: capacity.saturating_sub(cursor),
+    })
+}
+
+// How many entries at the head of `list` meet `predicate`, given that all of
+// those that do come before all of those that don't. kv-store can't say how
+// long a list is, so with a predicate that always holds this gives that too.
+// Takes a round trip for each bit of the answer, twice over.
+async fn partition_point<T: KeyValueStore>(
+    list: &str,
+    predicate: impl Fn(&str) -> RpcResult<bool>,
+    store: &mut T,
+) -> RpcResult<usize> {
+    let meets = |entry: Option<String>| match entry {
+        Some(entry) => predicate(&entry),
+        None => Ok(false),
+    };
+
+    // Entries before `low` meet it. `high` doubles until the entry there
+    // doesn't, and then the first that doesn't is narrowed in on.
+    let (mut low, mut high) = (0, 0);
+    while meets(entry(list, high, store).await?)? {
+        low = high + 1;
+        high = high * 2 + 1;
+    }
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if meets(entry(list, middle, store).await?)? {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    Ok(low)
+}
+
+async fn entry<T: KeyValueStore>(
+    list: &str,
+    index: usize,
+    store: &mut T,
+) -> RpcResult<Option<String>> {
+    let index = index as i32;
+    Ok(store
+        .list_range(list, index, index)
+        .await?
+        .into_iter()
+        .next())
+}
+
+fn decode(entry: &str) -> RpcResult<Used> {
+    json::from_str(entry).map_err(|error| RpcError::Deser(error.to_string()))
+}
+
+// Moves expired indexes from the used list back onto the free list, telling
+// how many there were. Going by how long `expires` holds an index now, only
+// the head of the list that was handed out at least that long ago needs
+// looking at. Any that are held for longer are passed over until they expire.
+async fn reclaim<T: KeyValueStore>(
+    scheme: Scheme<'_>,
+    expires: Duration,
+    rng: &mut Rng,
+    store: &mut T,
+) -> RpcResult<usize> {
+    let used_key = scheme.key(USED_KEY);
+    let now = clock::now();
+    let mut reclaimed = Vec::new();
+    // How many at the head are being passed over.
+    let mut held = 0;
+
+    'reclaiming: loop {
+        let head = store
+            .list_range(&used_key, held, held + RECLAIM_BATCH - 1)
+            .await?;
+        if head.is_empty() {
+            break;
+        }
+
+        for entry in head {
+            let used = decode(&entry)?;
+            if used.handed_at + expires > now {
+                break 'reclaiming;
+            }
+
+            if used.expires_at > now {
+                held += 1;
+                continue;
+            }
+
+            // Whoever deletes it from the used list is the one to free it.
+            if store.list_del(&used_key, &entry).await? {
+                reclaimed.push(used.index);
+            }
+        }
+    }
+
+    reclaimed.shuffle(rng);
+    push(&scheme.key(FREE_KEY), &reclaimed, store).await?;
+    Ok(reclaimed.len())
+}
+
+async fn push<T: KeyValueStore>(
+    free_key: &str,
+    indexes: &[usize],
+    store: &mut T,
+) -> RpcResult<()> {
+    for index in indexes {
+        store.list_push(free_key, &index.to_string()).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Codebook;
+    use super::*;
+    use crate::{key_value_store, random};
+    use std::collections::HashSet;
+
+    #[test]
+    fn it_permutes() {
+        for n in [1, 2, 3, 17, 153, 23_409] {
+            let mut indexes: Vec<_> =
+                (0..n).map(|i| permute(i, n, 7)).collect();
+            indexes.sort();
+            assert_eq!(indexes, (0..n).collect::<Vec<_>>());
+        }
+
+        let order = |seed| (0..100).map(|i| permute(i, 100, seed)).collect();
+        let orders: Vec<Vec<_>> = vec![order(1), order(1), order(2)];
+        assert_eq!(orders[0], orders[1]);
+        assert_ne!(orders[0], orders[2]);
+        assert_ne!(orders[0], (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn it_pops_without_filling() {
+        let mut store = key_value_store::InMemory::new();
+        let mut rng = random::from_seed(0);
+        let codebook = Codebook {
+            name: "numbers".to_string(),
+            words: (0..4).map(|i| i.to_string()).collect(),
+        };
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 2,
+        };
+
+        let expires = Duration::from_secs(60);
+        let mut popped = HashSet::new();
+        for popping in 0..scheme.capacity() {
+            let index = pop(scheme, expires, &mut rng, &mut store).await;
+            assert!(popped.insert(index.unwrap().unwrap()));
+
+            // Nothing is ever put on the free list up front.
+            if popping == 0 {
+                let free_key = scheme.key(FREE_KEY);
+                let free = store.list_range(&free_key, 0, -1).await.unwrap();
+                assert!(free.is_empty());
+            }
+        }
+
+        let index = pop(scheme, expires, &mut rng, &mut store).await;
+        assert_eq!(index.unwrap(), None);
+        let cursor = store.get(&scheme.key(CURSOR_KEY)).await.unwrap();
+        assert_eq!(cursor.unwrap(), scheme.capacity().to_string());
+    }
+
+    #[tokio::test]
+    async fn it_finds_partition_points() {
+        let mut store = key_value_store::InMemory::new();
+        let list = "partyskunk:numbers";
+        for length in 0..40 {
+            let is_small =
+                |entry: &str| Ok(entry.parse::<usize>().unwrap() < 9);
+            let all = partition_point(list, |_| Ok(true), &mut store).await;
+            assert_eq!(all.unwrap(), length);
+            let small = partition_point(list, is_small, &mut store).await;
+            assert_eq!(small.unwrap(), length.min(9));
+
+            store.list_push(list, &length.to_string()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reclaims_past_indexes_held_for_longer() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let mut rng = random::from_seed(0);
+        let codebook = Codebook {
+            name: "numbers".to_string(),
+            words: vec!["0".to_string(), "1".to_string()],
+        };
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 1,
+        };
+
+        let (long, short) = (Duration::from_secs(600), Duration::from_secs(60));
+        let held = pop(scheme, long, &mut rng, &mut store).await.unwrap();
+        let index = pop(scheme, short, &mut rng, &mut store).await.unwrap();
+        let popped = pop(scheme, short, &mut rng, &mut store).await.unwrap();
+        assert_eq!(popped, None);
+
+        // The index held for longer is at the head, and still in use.
+        tokio::time::advance(short).await;
+        let popped = pop(scheme, short, &mut rng, &mut store).await.unwrap();
+        assert_eq!(popped, index);
+        let popped = pop(scheme, short, &mut rng, &mut store).await.unwrap();
+        assert_eq!(popped, None);
+
+        tokio::time::advance(long).await;
+        let popped = pop(scheme, short, &mut rng, &mut store).await.unwrap();
+        assert_eq!(popped, held);
+    }
+
+    #[tokio::test]
+    async fn it_counts_indexes_held_for_longer_as_in_use() {
+        tokio::time::pause();
+        let mut store = key_value_store::InMemory::new();
+        let mut rng = random::from_seed(0);
+        let codebook = Codebook {
+            name: "numbers".to_string(),
+            words: (0..4).map(|i| i.to_string()).collect(),
+        };
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 1,
+        };
+
+        let (long, short) = (Duration::from_secs(600), Duration::from_secs(60));
+        pop(scheme, long, &mut rng, &mut store).await.unwrap();
+        let long_expires_at = clock::now() + long;
+        pop(scheme, short, &mut rng, &mut store).await.unwrap();
+        let counted = usage(scheme, short, &mut store).await.unwrap();
+        assert_eq!((counted.in_use, counted.used_listed), (2, 2));
+        assert_eq!(counted.next_expires_at, Some(clock::now() + short));
+        assert_eq!((counted.free_listed, counted.fresh), (0, 2));
+
+        // Neither has been reclaimed, but only the one held for longer is
+        // still in use, at the head of the used list.
+        tokio::time::advance(short).await;
+        let counted = usage(scheme, short, &mut store).await.unwrap();
+        assert_eq!((counted.in_use, counted.used_listed), (1, 2));
+        assert_eq!(counted.next_expires_at, Some(long_expires_at));
+    }
+}
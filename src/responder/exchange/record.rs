@@ -0,0 +1,227 @@
+use super::seal::{self, Sealed};
+use crate::{clock, config::Secret, random::Rng};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use sha2::{Digest, Sha256};
+use tokio::time::Duration;
+use wasmbus_rpc::actor::prelude::*;
+
+// Bump when the shape of `Record` changes in a way that `serde(default)` can't
+// paper over, and teach `Record::decode` about the old one.
+const VERSION: u32 = 1;
+
+// What's stored under a code. Codes created before records existed hold just
+// the message, which decodes as a version 0 record.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Record {
+    pub version: u32,
+    // Left empty once sealed.
+    #[serde(default)]
+    pub message: String,
+    pub creator: Option<String>,
+    // Both since the unix epoch.
+    pub created_at: Option<Duration>,
+    pub expires_at: Option<Duration>,
+    // Hashed along with the code, so equal PINs don't look alike.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_hash: Option<String>,
+    // Phone numbers that can read the message, besides the creator.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub receipts: bool,
+    // Since the unix epoch. The message can't be read before then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reveal_at: Option<Duration>,
+    // Records from before messages were sealed hold them in the open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<Sealed>,
+}
+
+impl Record {
+    pub fn new(
+        message: String,
+        creator: String,
+        created_at: Duration,
+        expires: Duration,
+    ) -> Record {
+        Record {
+            version: VERSION,
+            message,
+            creator: Some(creator),
+            created_at: Some(created_at),
+            expires_at: Some(created_at + expires),
+            pin_hash: None,
+            recipients: Vec::new(),
+            receipts: false,
+            reveal_at: None,
+            sealed: None,
+        }
+    }
+
+    // Legacy records don't know their expiry.
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| expires_at.saturating_sub(clock::now()))
+    }
+
+    pub fn allows_reader(&self, reader: &str) -> bool {
+        self.recipients.is_empty()
+            || self.creator.as_deref() == Some(reader)
+            || self.recipients.iter().any(|recipient| recipient == reader)
+    }
+
+    // Seals the message away under `code` and `pin`, which are needed to open
+    // it again.
+    pub fn seal(
+        &mut self,
+        secrets: &[Secret],
+        code: &str,
+        pin: Option<&str>,
+        rng: &mut Rng,
+    ) -> RpcResult<()> {
+        self.sealed = Some(seal::seal(secrets, &self.message, code, pin, rng)?);
+        self.message.clear();
+        Ok(())
+    }
+
+    pub fn open(
+        &self,
+        secrets: &[Secret],
+        code: &str,
+        pin: Option<&str>,
+    ) -> RpcResult<String> {
+        match &self.sealed {
+            Some(sealed) => seal::open(secrets, sealed, code, pin),
+            None => Ok(self.message.clone()),
+        }
+    }
+
+    pub fn set_pin(&mut self, code: &str, pin: &str) {
+        self.pin_hash = Some(hash_pin(code, pin));
+    }
+
+    // Records without a PIN accept any, or none.
+    pub fn accepts_pin(&self, code: &str, pin: Option<&str>) -> bool {
+        match (&self.pin_hash, pin) {
+            (None, _) => true,
+            (Some(hash), Some(pin)) => *hash == hash_pin(code, pin),
+            (Some(_), None) => false,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    pub fn encode(&self) -> RpcResult<String> {
+        json::to_string(self).map_err(|error| RpcError::Ser(error.to_string()))
+    }
+
+    // Only values that aren't JSON objects are taken for legacy messages, so
+    // that a record that won't decode is an error rather than read out as is.
+    pub fn decode(value: String) -> RpcResult<Record> {
+        match json::from_str::<json::Value>(&value) {
+            Ok(json::Value::Object(_)) => json::from_str(&value)
+                .map_err(|error| RpcError::Deser(format!("record: {}", error))),
+            _ => Ok(Record {
+                version: 0,
+                message: value,
+                creator: None,
+                created_at: None,
+                expires_at: None,
+                pin_hash: None,
+                recipients: Vec::new(),
+                receipts: false,
+                reveal_at: None,
+                sealed: None,
+            }),
+        }
+    }
+}
+
+// There are only so many short PINs, so this only keeps them from being read
+// straight out of the store. Locking out guessers is what protects them.
+fn hash_pin(code: &str, pin: &str) -> String {
+    let hash = Sha256::digest(format!("{}:{}", code, pin));
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::random;
+
+    #[test]
+    fn it_round_trips() {
+        let record = Record::new(
+            "message".to_string(),
+            "creator".to_string(),
+            Duration::from_secs(10),
+            Duration::from_secs(5),
+        );
+
+        let record = Record::decode(record.encode().unwrap()).unwrap();
+        assert_eq!(record.version, VERSION);
+        assert_eq!(record.message, "message");
+        assert_eq!(record.creator.unwrap(), "creator");
+        assert_eq!(record.expires_at.unwrap(), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn it_checks_pins() {
+        let mut record = Record::new(
+            "message".to_string(),
+            "creator".to_string(),
+            Duration::from_secs(10),
+            Duration::from_secs(5),
+        );
+        assert!(record.accepts_pin("apple", None));
+        assert!(record.accepts_pin("apple", Some("1234")));
+
+        record.set_pin("apple", "4821");
+        let record = Record::decode(record.encode().unwrap()).unwrap();
+        assert!(!record.encode().unwrap().contains("4821"));
+        assert!(record.accepts_pin("apple", Some("4821")));
+        assert!(!record.accepts_pin("apple", Some("1234")));
+        assert!(!record.accepts_pin("apple", None));
+        assert!(!record.accepts_pin("boat", Some("4821")));
+    }
+
+    #[test]
+    fn it_seals_messages() {
+        let mut record = Record::new(
+            "meet at noon".to_string(),
+            "creator".to_string(),
+            Duration::from_secs(10),
+            Duration::from_secs(5),
+        );
+        let secrets = seal::test_secrets(&[("1", "first")]);
+        let mut rng = random::from_seed(0);
+        record.seal(&secrets, "apple", None, &mut rng).unwrap();
+
+        let encoded = record.encode().unwrap();
+        assert!(!encoded.contains("noon"));
+        let record = Record::decode(encoded).unwrap();
+        let opened = record.open(&secrets, "apple", None).unwrap();
+        assert_eq!(opened, "meet at noon");
+        assert!(record.open(&secrets, "boat", None).is_err());
+    }
+
+    #[test]
+    fn it_decodes_legacy_messages() {
+        for message in ["plain message", "[\"a\", \"list\"]", "42"] {
+            let record = Record::decode(message.to_string()).unwrap();
+            assert!(record.is_legacy());
+            assert_eq!(record.message, message);
+            assert_eq!(record.creator, None);
+        }
+    }
+
+    #[test]
+    fn it_fails_on_corrupt_records() {
+        for value in ["{\"not\": \"a record\"}", "{\"version\": \"1\"}"] {
+            assert!(Record::decode(value.to_string()).is_err());
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use super::codebook::Codebook;
+
+// How many words from a codebook make up a code. A single word only gives as
+// many codes as there are words, while each additional word multiplies that
+// by the number of words again.
+#[derive(Clone, Copy, Debug)]
+pub struct Scheme<'a> {
+    pub codebook: &'a Codebook,
+    pub words: u32,
+}
+
+impl Scheme<'_> {
+    pub fn capacity(&self) -> usize {
+        self.codebook.words.len().pow(self.words)
+    }
+
+    // Reads `index` as a number in base `self.codebook.words.len()` with one
+    // digit per word.
+    pub fn code(&self, index: usize) -> String {
+        let words = &self.codebook.words;
+        let mut index = index % self.capacity();
+        let mut code = Vec::new();
+        for _ in 0..self.words {
+            code.push(words[index % words.len()].as_str());
+            index /= words.len();
+        }
+
+        code.reverse();
+        code.join(" ")
+    }
+
+    // Whether `code` is one of the scheme's codes, active or not.
+    pub fn contains(&self, code: &str) -> bool {
+        let words: Vec<_> = code.split(' ').collect();
+        words.len() == self.words as usize
+            && words
+                .iter()
+                .all(|word| self.codebook.words.iter().any(|w| w == word))
+    }
+
+    // Each scheme has its own codes, so anything keeping track of them needs
+    // its own key per codebook and number of words.
+    pub fn key(&self, key: &str) -> String {
+        let codebook = self.codebook;
+        format!(
+            "{}:{}:{}:{}",
+            key,
+            codebook.name,
+            codebook.fingerprint(),
+            self.words
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_maps_indexes_to_word_tuples() {
+        let codebook = Codebook::builtin("basic").unwrap();
+
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 1,
+        };
+        assert_eq!(scheme.capacity(), 2);
+        assert_eq!(scheme.code(0), "hello");
+        assert_eq!(scheme.code(3), "goodbye");
+        let key = format!("key:basic:{}:1", codebook.fingerprint());
+        assert_eq!(scheme.key("key"), key);
+
+        let scheme = Scheme {
+            codebook: &codebook,
+            words: 2,
+        };
+        assert_eq!(scheme.capacity(), 4);
+        let codes: Vec<_> = (0..5).map(|index| scheme.code(index)).collect();
+        assert_eq!(
+            codes,
+            vec![
+                "hello hello",
+                "hello goodbye",
+                "goodbye hello",
+                "goodbye goodbye",
+                "hello hello",
+            ]
+        );
+        assert!(scheme.contains("goodbye hello"));
+        assert!(!scheme.contains("goodbye"));
+        assert!(!scheme.contains("goodbye hallo"));
+        let key = format!("key:basic:{}:2", codebook.fingerprint());
+        assert_eq!(scheme.key("key"), key);
+    }
+}
@@ -0,0 +1,173 @@
+// Messages are encrypted at rest, under a key derived from a secret kept in
+// the secrets store along with the code and any PIN. A dump of the messages
+// alone doesn't give the secret, and the secret alone doesn't give the codes,
+// so neither is enough to read what's stored.
+use crate::{config::Secret, random::Rng};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use wasmbus_rpc::actor::prelude::*;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Sealed {
+    // Which secret the message was sealed with.
+    key_id: String,
+    // All base64.
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Seals with the first of `secrets`, failing when there are none.
+pub fn seal(
+    secrets: &[Secret],
+    message: &str,
+    code: &str,
+    pin: Option<&str>,
+    rng: &mut Rng,
+) -> RpcResult<Sealed> {
+    let Secret { id, secret } = secrets.first().ok_or_else(|| {
+        RpcError::Other("no secrets to seal with".to_string())
+    })?;
+
+    // A fresh salt gives each message its own key, even when a code comes
+    // around again.
+    let mut salt = [0; SALT_LENGTH];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0; NONCE_LENGTH];
+    rng.fill_bytes(&mut nonce);
+
+    let cipher = cipher(secret, &salt, code, pin)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), message.as_bytes())
+        .map_err(|error| RpcError::Other(error.to_string()))?;
+
+    Ok(Sealed {
+        key_id: id.clone(),
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+// Fails when the secret the message was sealed with is gone, or when the code
+// or PIN are wrong.
+pub fn open(
+    secrets: &[Secret],
+    sealed: &Sealed,
+    code: &str,
+    pin: Option<&str>,
+) -> RpcResult<String> {
+    let Secret { secret, .. } = secrets
+        .iter()
+        .find(|secret| secret.id == sealed.key_id)
+        .ok_or_else(|| {
+            RpcError::Other(format!("no secret {}", sealed.key_id))
+        })?;
+
+    let decode = |value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|error| RpcError::Deser(error.to_string()))
+    };
+    let nonce = decode(&sealed.nonce)?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err(RpcError::Deser("nonce length".to_string()));
+    }
+
+    let cipher = cipher(secret, &decode(&sealed.salt)?, code, pin)?;
+    let message = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            decode(&sealed.ciphertext)?.as_ref(),
+        )
+        .map_err(|error| RpcError::Other(error.to_string()))?;
+    String::from_utf8(message)
+        .map_err(|error| RpcError::Deser(error.to_string()))
+}
+
+fn cipher(
+    secret: &str,
+    salt: &[u8],
+    code: &str,
+    pin: Option<&str>,
+) -> RpcResult<ChaCha20Poly1305> {
+    let info = format!("partyskunk:{}:{}", code, pin.unwrap_or_default());
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(salt), secret.as_bytes())
+        .expand(info.as_bytes(), &mut key)
+        .map_err(|error| RpcError::Other(error.to_string()))?;
+    Ok(ChaCha20Poly1305::new(&key))
+}
+
+#[cfg(test)]
+pub fn test_secrets(secrets: &[(&str, &str)]) -> Vec<Secret> {
+    secrets
+        .iter()
+        .map(|(id, secret)| Secret {
+            id: id.to_string(),
+            secret: secret.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::random;
+
+    #[test]
+    fn it_opens_only_with_the_same_code_and_pin() {
+        let secrets = test_secrets(&[("2", "second"), ("1", "first")]);
+        let mut rng = random::from_seed(0);
+        let sealed = seal(&secrets, "message", "apple", Some("4821"), &mut rng);
+        let sealed = sealed.unwrap();
+        assert_eq!(sealed.key_id, "2");
+        assert!(!sealed.ciphertext.contains("message"));
+
+        let open = |code, pin| open(&secrets, &sealed, code, pin);
+        assert_eq!(open("apple", Some("4821")).unwrap(), "message");
+        assert!(open("apple", Some("1234")).is_err());
+        assert!(open("apple", None).is_err());
+        assert!(open("boat", Some("4821")).is_err());
+
+        // The same message under the same code seals differently each time.
+        let again = seal(&secrets, "message", "apple", Some("4821"), &mut rng);
+        assert_ne!(again.unwrap().ciphertext, sealed.ciphertext);
+    }
+
+    #[test]
+    fn it_opens_with_rotated_secrets() {
+        let mut rng = random::from_seed(0);
+        let old = test_secrets(&[("1", "first")]);
+        let sealed = seal(&old, "message", "apple", None, &mut rng).unwrap();
+
+        // Still opens once a newer secret has been added in front.
+        let rotated = test_secrets(&[("2", "second"), ("1", "first")]);
+        let opened = open(&rotated, &sealed, "apple", None).unwrap();
+        assert_eq!(opened, "message");
+
+        // But not once the old secret has been dropped.
+        let new = test_secrets(&[("2", "second")]);
+        assert!(open(&new, &sealed, "apple", None).is_err());
+
+        // Nor under a secret by the same name that isn't the same.
+        let wrong = test_secrets(&[("1", "wrong")]);
+        assert!(open(&wrong, &sealed, "apple", None).is_err());
+    }
+
+    #[test]
+    fn it_refuses_to_seal_without_secrets() {
+        let mut rng = random::from_seed(0);
+        assert!(seal(&[], "message", "apple", None, &mut rng).is_err());
+    }
+}
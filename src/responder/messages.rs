@@ -1,6 +1,30 @@
+use super::segments;
+use crate::config::Config;
 use indoc::indoc;
 use serde::Serialize;
 use tinytemplate::TinyTemplate;
+use tokio::time::Duration;
+
+// Renders the deployment's own template where it has one that renders, and
+// the default otherwise. Nothing is escaped, since none of it is HTML.
+fn render<C: Serialize>(
+    template: Option<&str>,
+    default: &str,
+    context: &C,
+) -> String {
+    let mut tt = TinyTemplate::new();
+    tt.set_default_formatter(&tinytemplate::format_unescaped);
+    if let Some(template) = template {
+        if tt.add_template("custom", template).is_ok() {
+            if let Ok(rendered) = tt.render("custom", context) {
+                return rendered;
+            }
+        }
+    }
+
+    tt.add_template("default", default).unwrap();
+    tt.render("default", context).unwrap()
+}
 
 static CREATE_SUCCESS_MESSAGE_TEMPLATE: &str = indoc! {"
     Got it. Here's your code word: {code}
@@ -12,17 +36,269 @@ struct CreateSuccessContext {
     code: String,
 }
 
-pub fn create_success(code: String) -> String {
-    let mut tt = TinyTemplate::new();
-    tt.add_template("success", CREATE_SUCCESS_MESSAGE_TEMPLATE)
-        .unwrap();
-    tt.render("success", &CreateSuccessContext { code })
-        .unwrap()
+pub fn create_success(config: &Config, code: String) -> String {
+    render(
+        config.templates.create_success.as_deref(),
+        CREATE_SUCCESS_MESSAGE_TEMPLATE,
+        &CreateSuccessContext { code },
+    )
+}
+
+static LIST_EMPTY_MESSAGE: &str = "You don't have any active code words.";
+
+static LIST_HEADER_MESSAGE: &str = "Your active code words:";
+
+// Lists as many as fit in the configured number of SMS segments.
+pub fn list_success(
+    config: &Config,
+    listings: Vec<(String, Duration)>,
+) -> String {
+    if listings.is_empty() {
+        return format!("{}\n\n{}", LIST_EMPTY_MESSAGE, help_pointer(config));
+    }
+
+    let segments = config.reply_segments;
+    let count = listings.len();
+    let mut reply = LIST_HEADER_MESSAGE.to_string();
+    for (listed, (code, expires_in)) in listings.into_iter().enumerate() {
+        let line = format!("\n{} (expires in {})", code, duration(expires_in));
+
+        // Unless this is the last one, leave room to say how many are left.
+        let rest = count - listed - 1;
+        let needed = match rest {
+            0 => line.clone(),
+            rest => format!("{}{}", line, list_omitted(rest)),
+        };
+
+        if segments::count(&format!("{}{}", reply, needed)) > segments {
+            reply.push_str(&list_omitted(count - listed));
+            break;
+        }
+
+        reply.push_str(&line);
+    }
+
+    reply
+}
+
+fn list_omitted(count: usize) -> String {
+    format!("\n...and {} more", count)
+}
+
+fn duration(duration: Duration) -> String {
+    let minutes = duration.as_secs().div_ceil(60);
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+static INBOX_EMPTY_MESSAGE: &str =
+    "You don't have any replies to your code words.";
+
+static INBOX_HEADER_MESSAGE: &str = "Replies to your code words:";
+
+pub fn inbox_success(
+    config: &Config,
+    replies: Vec<(String, String)>,
+    remaining: usize,
+) -> String {
+    if replies.is_empty() {
+        return INBOX_EMPTY_MESSAGE.to_string();
+    }
+
+    let mut reply = INBOX_HEADER_MESSAGE.to_string();
+    for (code, text) in replies {
+        reply.push_str(&format!("\n\n\"{}\": {}", code, text));
+    }
+    if remaining > 0 {
+        reply.push_str(&format!(
+            "\n\n...and {} more. Text {} inbox again to see them.",
+            remaining, config.create_word
+        ));
+    }
+
+    reply
+}
+
+static RELAYED_REPLY_MESSAGE_TEMPLATE: &str =
+    "Reply to your code word \"{code}\": {reply}";
+
+#[derive(Serialize)]
+struct RelayedReplyContext<'a> {
+    code: &'a str,
+    reply: &'a str,
+}
+
+// Sent to the creator, with nothing to say who it's from.
+pub fn relayed_reply(config: &Config, code: &str, reply: &str) -> String {
+    render(
+        config.templates.relayed_reply.as_deref(),
+        RELAYED_REPLY_MESSAGE_TEMPLATE,
+        &RelayedReplyContext { code, reply },
+    )
+}
+
+static REPLY_SENT_MESSAGE: &str =
+    "Got it. Your reply was passed on, without your number.";
+
+pub fn reply_sent() -> String {
+    REPLY_SENT_MESSAGE.to_string()
+}
+
+static REPLY_QUEUED_MESSAGE: &str =
+    "Got it. Your reply will be passed on, without your number.";
+
+pub fn reply_queued() -> String {
+    REPLY_QUEUED_MESSAGE.to_string()
+}
+
+static REPLY_REFUSED_ERROR_MESSAGE: &str =
+    "Sorry! That reply can't be passed on. Please try something else.";
+
+pub fn reply_refused_error() -> String {
+    REPLY_REFUSED_ERROR_MESSAGE.to_string()
+}
+
+static REPLY_TOO_MANY_ERROR_MESSAGE: &str =
+    "Whoops! That's a lot of replies to one code word. Please wait a while before sending more.";
+
+pub fn reply_too_many_error() -> String {
+    REPLY_TOO_MANY_ERROR_MESSAGE.to_string()
+}
+
+pub fn status_success(
+    free: usize,
+    in_use: usize,
+    next_free_in: Option<Duration>,
+) -> String {
+    let next = match next_free_in {
+        Some(next_free_in) => {
+            format!("The next one frees up in {}.", duration(next_free_in))
+        }
+        None => "None are waiting to free up.".to_string(),
+    };
+    format!(
+        "Code words free: {}\nCode words in use: {}\n{}",
+        free, in_use, next
+    )
+}
+
+// Appended to the status for admins.
+pub fn status_detail(
+    codebook: &str,
+    codebook_size: usize,
+    code_words: u32,
+    capacity: usize,
+    free_listed: usize,
+    used_listed: usize,
+    fresh: usize,
+) -> String {
+    format!(
+        "Codebook: {} ({} words, {} per code)\nCapacity: {}\nFree list: {}\nUsed list: {}\nNever handed out: {}",
+        codebook,
+        codebook_size,
+        code_words,
+        capacity,
+        free_listed,
+        used_listed,
+        fresh
+    )
+}
+
+pub fn admin_purged(code: &str, purged: bool) -> String {
+    if purged {
+        format!("Purged \"{}\".", code)
+    } else {
+        format!("There's nothing at \"{}\" to purge.", code)
+    }
+}
+
+pub fn admin_banned(number: &str) -> String {
+    format!("Banned {}.", number)
+}
+
+pub fn admin_unbanned(number: &str, banned: bool) -> String {
+    if banned {
+        format!("Unbanned {}.", number)
+    } else {
+        format!("{} wasn't banned.", number)
+    }
+}
+
+pub fn admin_maintenance(on: bool) -> String {
+    if on {
+        "Maintenance mode is on. Only admins are being answered.".to_string()
+    } else {
+        "Maintenance mode is off.".to_string()
+    }
+}
+
+static BANNED_ERROR_MESSAGE: &str =
+    "Sorry, this number can't use this service.";
+
+pub fn banned_error() -> String {
+    BANNED_ERROR_MESSAGE.to_string()
+}
+
+static MAINTENANCE_ERROR_MESSAGE: &str =
+    "Whoops! We're down for maintenance. Try again later.";
+
+pub fn maintenance_error() -> String {
+    MAINTENANCE_ERROR_MESSAGE.to_string()
 }
 
 static UNKNOWN_ERROR_MESSAGE: &str =
     "Whoops! Something went wrong. Try again later.";
 
+static RECEIPT_MESSAGE_TEMPLATE: &str =
+    "Your code word \"{code}\" was read at {read_at} by {reader}.";
+
+#[derive(Serialize)]
+struct ReceiptContext<'a> {
+    code: &'a str,
+    read_at: String,
+    reader: String,
+}
+
+// Sent to the creator, with all but the end of the reader's number masked.
+pub fn receipt(
+    config: &Config,
+    code: &str,
+    reader: &str,
+    read_at: Duration,
+) -> String {
+    let masked = reader.chars().count().saturating_sub(4);
+    let shown: String = reader.chars().skip(masked).collect();
+    let reader = format!("{}{}", "*".repeat(masked), shown);
+    render(
+        config.templates.receipt.as_deref(),
+        RECEIPT_MESSAGE_TEMPLATE,
+        &ReceiptContext {
+            code,
+            read_at: time_of_day(read_at),
+            reader,
+        },
+    )
+}
+
+// Like "3:14pm UTC", from a time since the unix epoch.
+fn time_of_day(time: Duration) -> String {
+    let minutes = time.as_secs() / 60;
+    let (hour, minute) = ((minutes / 60) % 24, minutes % 60);
+    let suffix = if hour < 12 { "am" } else { "pm" };
+    let hour = match hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    format!("{}:{:02}{} UTC", hour, minute, suffix)
+}
+
+pub fn unknown_error() -> String {
+    UNKNOWN_ERROR_MESSAGE.to_string()
+}
+
 pub fn create_unknown_error() -> String {
     UNKNOWN_ERROR_MESSAGE.to_string()
 }
@@ -31,43 +307,271 @@ pub fn find_unknown_error() -> String {
     UNKNOWN_ERROR_MESSAGE.to_string()
 }
 
+pub fn list_unknown_error() -> String {
+    UNKNOWN_ERROR_MESSAGE.to_string()
+}
+
+pub fn status_unknown_error() -> String {
+    UNKNOWN_ERROR_MESSAGE.to_string()
+}
+
+pub fn admin_unknown_error() -> String {
+    UNKNOWN_ERROR_MESSAGE.to_string()
+}
+
+pub fn inbox_unknown_error() -> String {
+    UNKNOWN_ERROR_MESSAGE.to_string()
+}
+
 static CREATE_OVER_CAPACITY_ERROR_MESSAGE: &str =
     "Whoops! The code word database is full. Try again later.";
 
-pub fn create_over_capacity_error() -> String {
-    CREATE_OVER_CAPACITY_ERROR_MESSAGE.to_string()
+pub fn create_over_capacity_error(config: &Config) -> String {
+    format!(
+        "{} To see when a code word frees up, text: {} status",
+        CREATE_OVER_CAPACITY_ERROR_MESSAGE, config.create_word
+    )
+}
+
+static CREATE_TAKEN_ERROR_MESSAGE: &str =
+    "Whoops! That code word is taken. Try another, or leave it out to get one at random.";
+
+pub fn create_taken_error() -> String {
+    CREATE_TAKEN_ERROR_MESSAGE.to_string()
+}
+
+static CREATE_NOT_CODE_WORD_ERROR_MESSAGE: &str =
+    "Whoops! That word can't be a code word. Try another, or leave it out to get one at random.";
+
+pub fn create_not_code_word_error() -> String {
+    CREATE_NOT_CODE_WORD_ERROR_MESSAGE.to_string()
+}
+
+static CREATE_REVEAL_TOO_LATE_ERROR_MESSAGE: &str =
+    "Whoops! Messages can only be held back for less than 24 hours, since that's when code words expire.";
+
+pub fn create_reveal_too_late_error() -> String {
+    CREATE_REVEAL_TOO_LATE_ERROR_MESSAGE.to_string()
+}
+
+static CREATE_REFUSED_ERROR_MESSAGE: &str =
+    "Sorry! That message can't be sent here. Please try something else.";
+
+pub fn create_refused_error() -> String {
+    CREATE_REFUSED_ERROR_MESSAGE.to_string()
 }
 
 static FIND_NOT_FOUND_ERROR_MESSAGE: &str =
     "Whoops! That code word doesn't exist.";
+static FIND_EXPIRED_ERROR_MESSAGE: &str = "Whoops! That code word expired.";
+static FIND_INACTIVE_ERROR_MESSAGE: &str =
+    "Whoops! That code word isn't active.";
+static FIND_NOT_CODE_WORD_ERROR_MESSAGE: &str =
+    "Whoops! That's not a code word.";
 
-static CREATE_HOW_TO_MESSAGE: &str = indoc! {"
-    Use partyskunk to create a code word for your message by sending us a text in this format:
-    partyskunk <your message here>
+// Everything there is to do, kept short enough to send as one reply.
+static HELP_MESSAGE_TEMPLATE: &str = indoc! {"
+    To get a code word for a message, text: {word} <message>
+    Before the message, add any of: as <code word>, pin <PIN>, for <numbers>, receipt, after <2h>
+    To read one, text the code word. To reply: <code word> reply <text>
+    Also: {word} list, {word} inbox, {word} status"};
 
-    Code words expire after 24 hours.
-"};
+// Templates are given the create {word}.
+#[derive(Serialize)]
+struct HelpContext<'a> {
+    word: &'a str,
+}
 
-static FIND_HOW_TO_MESSAGE: &str =
-    "If someone gave you a code word, send us a text with just that code word.";
+// Given the configured create word.
+pub fn help(config: &Config) -> String {
+    render(
+        config.templates.help.as_deref(),
+        HELP_MESSAGE_TEMPLATE,
+        &HelpContext {
+            word: &config.create_word,
+        },
+    )
+}
+
+// Where everything else points instead of explaining it all again.
+fn help_pointer(config: &Config) -> String {
+    format!("For help, text: {} help", config.create_word)
+}
+
+pub fn find_expired_error(config: &Config) -> String {
+    find_not_found_error(config, FIND_EXPIRED_ERROR_MESSAGE)
+}
+
+pub fn find_inactive_error(config: &Config) -> String {
+    find_not_found_error(config, FIND_INACTIVE_ERROR_MESSAGE)
+}
+
+pub fn find_not_code_word_error(config: &Config) -> String {
+    find_not_found_error(config, FIND_NOT_CODE_WORD_ERROR_MESSAGE)
+}
 
-pub fn find_not_found_error() -> String {
+fn find_not_found_error(config: &Config, reason: &str) -> String {
+    format!("{}\n\n{}", reason, help_pointer(config))
+}
+
+static FIND_WRONG_PIN_ERROR_MESSAGE: &str =
+    "Whoops! That code word needs its PIN. Send the code word followed by the PIN, like: apple 1234";
+
+pub fn find_wrong_pin_error() -> String {
+    FIND_WRONG_PIN_ERROR_MESSAGE.to_string()
+}
+
+static FIND_LOCKED_OUT_ERROR_MESSAGE: &str =
+    "Whoops! Too many wrong PINs were sent for that code word, so it's locked.";
+
+pub fn find_locked_out_error() -> String {
+    FIND_LOCKED_OUT_ERROR_MESSAGE.to_string()
+}
+
+pub fn find_unrevealed_error(reveal_at: Duration) -> String {
     format!(
-        "{}\n\n{}\n\n{}",
+        "That code word's message isn't available yet. Try again after {}.",
+        time_of_day(reveal_at)
+    )
+}
+
+pub fn find_ambiguous_error(codes: Vec<String>) -> String {
+    let codes: Vec<_> =
+        codes.iter().map(|code| format!("\"{}\"", code)).collect();
+    format!(
+        "{} Did you mean {}?",
         FIND_NOT_FOUND_ERROR_MESSAGE,
-        CREATE_HOW_TO_MESSAGE,
-        FIND_HOW_TO_MESSAGE
+        codes.join(" or ")
     )
 }
 
-pub fn prompt_malformed_error() -> String {
-    format!("{}\n\n{}", CREATE_HOW_TO_MESSAGE, FIND_HOW_TO_MESSAGE)
+pub fn prompt_malformed_error(config: &Config) -> String {
+    help(config)
+}
+
+pub fn prompt_create_message_invalid_error(
+    config: &Config,
+    _reason: String,
+) -> String {
+    help(config)
 }
 
-pub fn prompt_create_message_invalid_error(_reason: String) -> String {
-    format!("{}\n\n{}", CREATE_HOW_TO_MESSAGE, FIND_HOW_TO_MESSAGE)
+// The message is what matters, so the pointer to help is left off of it
+// rather than have any of it cut short.
+pub fn find_success(config: &Config, message: String) -> String {
+    let pointed = format!("{}\n\n{}", message, help_pointer(config));
+    if segments::count(&pointed) <= config.reply_segments {
+        pointed
+    } else {
+        message
+    }
 }
 
-pub fn find_success(message: String) -> String {
-    format!("{}\n\n{}", message, CREATE_HOW_TO_MESSAGE)
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Templates;
+
+    #[test]
+    fn receipt_masks_the_reader() {
+        let config = Config::default();
+        let read_at = Duration::from_secs(15 * 3600 + 14 * 60 + 59);
+        assert_eq!(
+            receipt(&config, "apple", "+14105551234", read_at),
+            "Your code word \"apple\" was read at 3:14pm UTC by ********1234."
+        );
+        let read_at = Duration::from_secs(86_400 * 3 + 5 * 60);
+        assert!(receipt(&config, "apple", "12", read_at)
+            .ends_with("12:05am UTC by 12."));
+        // Not every reader is a plain phone number.
+        assert!(receipt(&config, "apple", "whatsapp:ébène", read_at)
+            .ends_with("by **********bène."));
+    }
+
+    #[test]
+    fn help_uses_the_create_word() {
+        let config = Config {
+            create_word: "memo".to_string(),
+            ..Config::default()
+        };
+        let reply = help(&config);
+        assert!(reply.contains("text: memo <message>"));
+        assert!(reply.contains("memo list"));
+        assert!(!reply.contains("partyskunk"));
+        assert!(find_expired_error(&config).ends_with("text: memo help"));
+
+        // Short enough to send whole under the default create word.
+        assert_eq!(segments::count(&help(&Config::default())), 2);
+    }
+
+    #[test]
+    fn find_success_points_to_help_where_it_fits() {
+        let config = Config::default();
+        let reply = find_success(&config, "meet at noon".to_string());
+        assert_eq!(reply, "meet at noon\n\nFor help, text: partyskunk help");
+
+        let message = "🎉".repeat(100);
+        let reply = find_success(&config, message.clone());
+        assert_eq!(reply, message);
+    }
+
+    #[test]
+    fn list_success_truncates_long_lists() {
+        let listings: Vec<_> = (0..40)
+            .map(|i| (format!("code{}", i), Duration::from_secs(3_600)))
+            .collect();
+
+        let config = |reply_segments| Config {
+            reply_segments,
+            ..Config::default()
+        };
+        let reply = list_success(&config(2), listings.clone());
+        assert_eq!(segments::count(&reply), 2);
+        assert!(
+            reply.starts_with("Your active code words:\ncode0 (expires in 1h)")
+        );
+
+        let listed = reply.matches("(expires in").count();
+        assert!(reply.ends_with(&format!("...and {} more", 40 - listed)));
+
+        let reply = list_success(&config(1), listings);
+        assert_eq!(segments::count(&reply), 1);
+        assert!(reply.matches("(expires in").count() < listed);
+
+        // Codes outside of GSM-7 leave room for fewer.
+        let listings: Vec<_> = (0..40)
+            .map(|i| (format!("fête{}", i), Duration::from_secs(3_600)))
+            .collect();
+        let reply = list_success(&config(2), listings);
+        assert_eq!(segments::count(&reply), 2);
+        assert!(reply.matches("(expires in").count() < listed);
+    }
+
+    #[test]
+    fn it_words_texts_with_templates() {
+        let config = Config {
+            create_word: "memo".to_string(),
+            templates: Templates {
+                create_success: Some("Blue team code: {code}".to_string()),
+                help: Some("Text {word} and a message.".to_string()),
+                relayed_reply: Some("{code} & co: {reply}".to_string()),
+                // Nothing to render it with.
+                receipt: Some("{code} read by {nobody}".to_string()),
+            },
+            ..Config::default()
+        };
+
+        let reply = create_success(&config, "apple".to_string());
+        assert_eq!(reply, "Blue team code: apple");
+        let reply = help(&config);
+        assert_eq!(reply, "Text memo and a message.");
+        let reply = relayed_reply(&config, "apple", "a < b");
+        assert_eq!(reply, "apple & co: a < b");
+        let read_at = Duration::from_secs(15 * 3600 + 14 * 60);
+        let reply = receipt(&config, "apple", "+14105551234", read_at);
+        assert_eq!(
+            reply,
+            receipt(&Config::default(), "apple", "+14105551234", read_at)
+        );
+    }
 }
@@ -0,0 +1,133 @@
+use crate::{classifier::Classifier, config::Moderation};
+use regex::Regex;
+use wasmbus_rpc::actor::prelude::*;
+
+// Why a message was refused. Creators are only ever told that it was, so as
+// not to help anyone word their way around it.
+#[derive(Debug, PartialEq)]
+pub enum Refusal {
+    Blocked,
+    Link(String),
+    Classified,
+}
+
+// Checks a message against the configured lists first, and only asks the
+// classifier about messages that make it past them.
+pub async fn moderate<C: Classifier>(
+    message: &str,
+    moderation: &Moderation,
+    classifier: &mut C,
+) -> RpcResult<Option<Refusal>> {
+    let words = normalize(message);
+    let blocked = moderation.blocklist.iter().any(|entry| {
+        let entry = normalize(entry);
+        !entry.trim().is_empty() && words.contains(&entry)
+    });
+    if blocked {
+        return Ok(Some(Refusal::Blocked));
+    }
+
+    for domain in domains(message) {
+        if !moderation.allows_domain(&domain) {
+            return Ok(Some(Refusal::Link(domain)));
+        }
+    }
+
+    if classifier.refuses(message).await? {
+        return Ok(Some(Refusal::Classified));
+    }
+
+    Ok(None)
+}
+
+// Lowercase words separated by single spaces, with a space either side, so
+// that blocklist entries only ever match whole words and phrases.
+fn normalize(text: &str) -> String {
+    let regex = Regex::new(r"[^\p{L}\p{N}]+").unwrap();
+    let words: Vec<_> = regex
+        .split(&text.to_lowercase())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
+// The domains of anything that looks like a link, with or without a scheme,
+// since phones make links of bare domains too.
+fn domains(message: &str) -> Vec<String> {
+    let regex = Regex::new(
+        r"(?i)\b(?:[a-z][a-z0-9+.-]*://)?(?P<domain>(?:[a-z0-9-]+\.)+[a-z]{2,})\b",
+    )
+    .unwrap();
+    regex
+        .captures_iter(message)
+        .map(|captures| captures["domain"].to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::classifier;
+
+    fn moderation() -> Moderation {
+        Moderation {
+            blocklist: vec!["slur".to_string(), "free money".to_string()],
+            allowed_domains: Vec::new(),
+            denied_domains: vec!["bit.ly".to_string()],
+        }
+    }
+
+    async fn check(message: &str, moderation: &Moderation) -> Option<Refusal> {
+        let mut classifier = classifier::StandIn::new(&["buy now"]);
+        moderate(message, moderation, &mut classifier)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_refuses_blocked_words() {
+        for message in ["you SLUR", "get free\nmoney!", "slur."] {
+            let refusal = check(message, &moderation()).await;
+            assert_eq!(refusal, Some(Refusal::Blocked));
+        }
+
+        for message in ["slurp", "free of money", "meet at noon"] {
+            assert_eq!(check(message, &moderation()).await, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_refuses_denied_links() {
+        let refusal = check("see https://Bit.ly/x", &moderation()).await;
+        assert_eq!(refusal, Some(Refusal::Link("bit.ly".to_string())));
+        let refusal = check("see go.bit.ly/x", &moderation()).await;
+        assert_eq!(refusal, Some(Refusal::Link("go.bit.ly".to_string())));
+        assert_eq!(check("see example.com", &moderation()).await, None);
+
+        // Only allowed domains, once there are any.
+        let moderation = Moderation {
+            allowed_domains: vec!["example.com".to_string()],
+            ..moderation()
+        };
+        for message in ["see example.com", "www.example.com/party"] {
+            assert_eq!(check(message, &moderation).await, None);
+        }
+        let refusal = check("see http://evil.example.net", &moderation).await;
+        let domain = "evil.example.net".to_string();
+        assert_eq!(refusal, Some(Refusal::Link(domain)));
+    }
+
+    #[tokio::test]
+    async fn it_asks_the_classifier_last() {
+        let refusal = check("buy now", &moderation()).await;
+        assert_eq!(refusal, Some(Refusal::Classified));
+
+        let mut classifier = classifier::StandIn::new(&[]);
+        let result =
+            moderate("classifier error", &moderation(), &mut classifier).await;
+        assert!(result.is_err());
+        let result = moderate("slur", &moderation(), &mut classifier).await;
+        assert_eq!(result.unwrap(), Some(Refusal::Blocked));
+    }
+}
@@ -1,51 +1,619 @@
+use super::segments;
+use crate::config::Config;
 use regex::Regex;
 use std::result;
+use tokio::time::Duration;
 
+#[derive(Debug, PartialEq)]
 pub enum Action {
     Create(String),
-    Read(String),
+    // A code chosen by the creator, then the message.
+    CreateAs(String, String),
+    // A PIN that readers have to give, then the message.
+    CreateWithPin(String, String),
+    // Phone numbers that can read the message, then the message.
+    CreateFor(Vec<String>, String),
+    // A message that the creator is told of each time it's read.
+    CreateWithReceipt(String),
+    // How long to hold the message back for, then the message.
+    CreateWithReveal(Duration, String),
+    // The code, then any PIN given with it.
+    Read(String, Option<String>),
+    // The code, any PIN given with it, then a reply for its creator.
+    Reply(String, Option<String>, String),
+    List,
+    Inbox,
+    Status,
+    Help,
+    // Only for admins.
+    Admin(AdminAction),
 }
 
+#[derive(Debug, PartialEq)]
+pub enum AdminAction {
+    // Takes down the message at a code.
+    Purge(String),
+    // Keeps a number from using the service, or lets it again.
+    Ban(String),
+    Unban(String),
+    Stats,
+    // Whether to turn everyone but admins away.
+    Maintenance(bool),
+}
+
+impl Action {
+    // Whether carrying the action out looks at `clock::now`.
+    pub fn needs_time(&self) -> bool {
+        match self {
+            Action::Create(_)
+            | Action::CreateAs(_, _)
+            | Action::CreateWithPin(_, _)
+            | Action::CreateFor(_, _)
+            | Action::CreateWithReceipt(_)
+            | Action::CreateWithReveal(_, _)
+            | Action::Read(_, _)
+            | Action::Reply(_, _, _)
+            | Action::List
+            | Action::Inbox
+            | Action::Status
+            | Action::Admin(_) => true,
+            Action::Help => false,
+        }
+    }
+
+    // Whatever would be passed on to someone else, either the message a code
+    // would be created for or a reply to its creator.
+    pub fn passed_on_text(&self) -> Option<&str> {
+        match self {
+            Action::Create(message)
+            | Action::CreateAs(_, message)
+            | Action::CreateWithPin(_, message)
+            | Action::CreateFor(_, message)
+            | Action::CreateWithReceipt(message)
+            | Action::CreateWithReveal(_, message)
+            | Action::Reply(_, _, message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub enum PromptParseError {
     MalformedAction,
     MessageInvalid(String),
 }
 
 type PromptParseResult = result::Result<Action, PromptParseError>;
-const CREATE_PROMPT_WORD: &str = "partyskunk";
+// Shared with `build.rs`, which keeps them out of codebooks.
+include!("parser/words.rs");
+// PINs are all digits, so they can't be mistaken for code words.
+const PIN_PATTERN: &str = r"\d{4,8}";
+// International numbers the way that they send from.
+const NUMBER_PATTERN: &str = r"\+\d{8,15}";
 const MESSAGE_CHARACTER_LIMIT: usize = 140;
+// Enough for the character limit in any language, but not for one made up
+// of emoji alone.
+const MESSAGE_SEGMENT_LIMIT: usize = 3;
 const MESSAGE_INVALID_REASON_MESSAGE: &str =
-    "Message must not be empty and must be less than 140 characters.";
+    "Message must not be empty and must be at most 140 characters.";
+const MESSAGE_SEGMENTS_REASON_MESSAGE: &str =
+    "Message must fit in 3 texts, and emoji take up more room.";
 
-pub fn parse(prompt: String) -> PromptParseResult {
+// Everything other than reading or replying to a code starts with the
+// configured create word, or one of its aliases.
+pub fn parse(prompt: String, config: &Config) -> PromptParseResult {
     let regex = Regex::new(r"\s+").unwrap();
     let mut split = regex.splitn(prompt.trim(), 2);
 
     match split.next() {
-        Some(code) => match code.to_lowercase().as_str() {
-            CREATE_PROMPT_WORD => match split.next() {
-                Some(message) => {
-                    if message.len() <= MESSAGE_CHARACTER_LIMIT {
-                        Ok(Action::Create(message.to_string()))
-                    } else {
-                        Err(PromptParseError::MessageInvalid(
-                            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
-                        ))
+        Some(word) if config.is_create_word(word) => match split.next() {
+            Some(message) if message.to_lowercase() == LIST_PROMPT_WORD => {
+                Ok(Action::List)
+            }
+            Some(message) if message.to_lowercase() == INBOX_PROMPT_WORD => {
+                Ok(Action::Inbox)
+            }
+            Some(message) if message.to_lowercase() == STATUS_PROMPT_WORD => {
+                Ok(Action::Status)
+            }
+            Some(message) if message.to_lowercase() == HELP_PROMPT_WORD => {
+                Ok(Action::Help)
+            }
+            Some(message) => {
+                let mut split = regex.splitn(message, 2);
+                let word = split.next().unwrap_or_default().to_lowercase();
+                let rest = split.next();
+                // Options only count when what follows reads as one, so a
+                // message that just starts with the same word is created as
+                // it is.
+                let option = match word.as_str() {
+                    CREATE_AS_PROMPT_WORD => parse_create_as(rest),
+                    CREATE_WITH_PIN_PROMPT_WORD => parse_create_with_pin(rest),
+                    CREATE_FOR_PROMPT_WORD => parse_create_for(rest),
+                    _ if word.strip_suffix(':')
+                        == Some(CREATE_WITH_RECEIPT_PROMPT_WORD) =>
+                    {
+                        parse_create_with_receipt(rest)
+                    }
+                    CREATE_WITH_REVEAL_PROMPT_WORD => {
+                        parse_create_with_reveal(rest)
                     }
+                    ADMIN_PROMPT_WORD => Some(parse_admin(rest)),
+                    _ => None,
+                };
+                match option {
+                    Some(action) => action,
+                    None => Ok(Action::Create(validate(message)?)),
                 }
-                None => Err(PromptParseError::MessageInvalid(
+            }
+            None => Err(PromptParseError::MessageInvalid(
+                MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+            )),
+        },
+        // Codes can't contain the reply word, so the first one splits a
+        // reply from the code it's for.
+        Some(_) => {
+            let regex =
+                Regex::new(&format!(r"(?i)\s+{}(\s+|$)", REPLY_PROMPT_WORD))
+                    .unwrap();
+            let mut split = regex.splitn(prompt.trim(), 2);
+            let (code, pin) = parse_code(split.next().unwrap_or_default())?;
+            match split.next() {
+                Some(reply) if !reply.is_empty() => {
+                    Ok(Action::Reply(code, pin, validate(reply)?))
+                }
+                Some(_) => Err(PromptParseError::MessageInvalid(
                     MESSAGE_INVALID_REASON_MESSAGE.to_string(),
                 )),
-            },
-            code => {
-                let regex = Regex::new(r"^[a-z]+$").unwrap();
-                if regex.is_match(code) {
-                    Ok(Action::Read(code.to_owned()))
-                } else {
-                    Err(PromptParseError::MalformedAction)
-                }
+                None => Ok(Action::Read(code, pin)),
             }
-        },
+        }
+        None => Err(PromptParseError::MalformedAction),
+    }
+}
+
+// Codes can be several words, which are read back in the canonical form of
+// being separated by a single space, whatever spacing or hyphenation was used
+// to send them. A PIN may follow.
+fn parse_code(
+    prompt: &str,
+) -> result::Result<(String, Option<String>), PromptParseError> {
+    let regex = Regex::new(&format!(
+        r"^(?P<code>[a-z]+([\s-]+[a-z]+)*)(\s+(?P<pin>{}))?$",
+        PIN_PATTERN
+    ))
+    .unwrap();
+    let prompt = prompt.trim().to_lowercase();
+    match regex.captures(&prompt) {
+        Some(captures) => {
+            let regex = Regex::new(r"[\s-]+").unwrap();
+            let code: Vec<_> = regex.split(&captures["code"]).collect();
+            let pin = captures.name("pin").map(|pin| pin.as_str().to_string());
+            Ok((code.join(" "), pin))
+        }
         None => Err(PromptParseError::MalformedAction),
     }
 }
+
+// Options give `None` when what follows them isn't their argument, and the
+// message is created as it is instead.
+
+// The code comes first, as a single word ended by a colon, so that messages
+// like "as soon as you land" aren't mistaken for one.
+fn parse_create_as(prompt: Option<&str>) -> Option<PromptParseResult> {
+    let regex = Regex::new(r"^(?P<code>[a-zA-Z]+):(\s+(?P<message>[\s\S]*))?$")
+        .unwrap();
+    let captures = regex.captures(prompt?)?;
+    let code = captures["code"].to_lowercase();
+
+    Some(match captures.name("message") {
+        Some(message) => validate(message.as_str())
+            .map(|message| Action::CreateAs(code, message)),
+        None => Err(PromptParseError::MessageInvalid(
+            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+        )),
+    })
+}
+
+// The PIN comes first.
+fn parse_create_with_pin(prompt: Option<&str>) -> Option<PromptParseResult> {
+    let regex = Regex::new(r"\s+").unwrap();
+    let mut split = regex.splitn(prompt?, 2);
+    let pin = split.next().unwrap_or_default();
+    let regex = Regex::new(&format!("^{}$", PIN_PATTERN)).unwrap();
+    if !regex.is_match(pin) {
+        return None;
+    }
+
+    Some(match split.next() {
+        Some(message) => validate(message)
+            .map(|message| Action::CreateWithPin(pin.to_string(), message)),
+        None => Err(PromptParseError::MessageInvalid(
+            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+        )),
+    })
+}
+
+// The delay comes first, in hours and minutes, like 2h, 45m or 1h30m.
+fn parse_create_with_reveal(prompt: Option<&str>) -> Option<PromptParseResult> {
+    let regex = Regex::new(r"\s+").unwrap();
+    let mut split = regex.splitn(prompt?, 2);
+    let delay = split.next().unwrap_or_default().to_lowercase();
+    let regex =
+        Regex::new(r"^((?P<hours>\d+)h)?((?P<minutes>\d+)m)?$").unwrap();
+    let captures = regex.captures(&delay).filter(|_| !delay.is_empty())?;
+
+    let count = |unit| {
+        captures
+            .name(unit)
+            .map_or(Some(0), |count| count.as_str().parse::<u64>().ok())
+    };
+    let delay = Duration::from_secs(count("hours")?.saturating_mul(3_600))
+        .saturating_add(Duration::from_secs(
+            count("minutes")?.saturating_mul(60),
+        ));
+
+    Some(match split.next() {
+        Some(message) => validate(message)
+            .map(|message| Action::CreateWithReveal(delay, message)),
+        None => Err(PromptParseError::MessageInvalid(
+            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+        )),
+    })
+}
+
+// Recipients come first, as international numbers separated by commas.
+fn parse_create_for(prompt: Option<&str>) -> Option<PromptParseResult> {
+    let regex = Regex::new(&format!(
+        r"^(?P<recipients>{0}(\s*,\s*{0})*)(\s+(?P<message>[\s\S]*))?$",
+        NUMBER_PATTERN
+    ))
+    .unwrap();
+    let captures = regex.captures(prompt?)?;
+
+    let regex = Regex::new(r"\s*,\s*").unwrap();
+    let recipients = regex
+        .split(&captures["recipients"])
+        .map(String::from)
+        .collect();
+    Some(match captures.name("message") {
+        Some(message) => validate(message.as_str())
+            .map(|message| Action::CreateFor(recipients, message)),
+        None => Err(PromptParseError::MessageInvalid(
+            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+        )),
+    })
+}
+
+// The word is ended by a colon, so that messages like "receipt attached"
+// aren't mistaken for asking for one.
+fn parse_create_with_receipt(
+    prompt: Option<&str>,
+) -> Option<PromptParseResult> {
+    Some(match prompt {
+        Some(message) => validate(message).map(Action::CreateWithReceipt),
+        None => Err(PromptParseError::MessageInvalid(
+            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+        )),
+    })
+}
+
+// The action comes first, then whatever it acts on. Anything unexpected is
+// malformed, the same as it would be for anyone else.
+fn parse_admin(prompt: Option<&str>) -> PromptParseResult {
+    let regex = Regex::new(r"\s+").unwrap();
+    let mut split = regex.splitn(prompt.unwrap_or_default(), 2);
+    let action = split.next().unwrap_or_default().to_lowercase();
+    let rest = split.next().map(str::trim);
+    let number = Regex::new(&format!("^{}$", NUMBER_PATTERN)).unwrap();
+    let action = match (action.as_str(), rest) {
+        ("purge", Some(code)) => match parse_code(code)? {
+            (code, None) => AdminAction::Purge(code),
+            (_, Some(_)) => return Err(PromptParseError::MalformedAction),
+        },
+        ("ban", Some(rest)) if number.is_match(rest) => {
+            AdminAction::Ban(rest.to_string())
+        }
+        ("unban", Some(rest)) if number.is_match(rest) => {
+            AdminAction::Unban(rest.to_string())
+        }
+        ("stats", None) => AdminAction::Stats,
+        ("maintenance", Some(rest)) => match rest.to_lowercase().as_str() {
+            "on" => AdminAction::Maintenance(true),
+            "off" => AdminAction::Maintenance(false),
+            _ => return Err(PromptParseError::MalformedAction),
+        },
+        _ => return Err(PromptParseError::MalformedAction),
+    };
+
+    Ok(Action::Admin(action))
+}
+
+fn validate(message: &str) -> result::Result<String, PromptParseError> {
+    if message.chars().count() > MESSAGE_CHARACTER_LIMIT {
+        Err(PromptParseError::MessageInvalid(
+            MESSAGE_INVALID_REASON_MESSAGE.to_string(),
+        ))
+    } else if segments::count(message) > MESSAGE_SEGMENT_LIMIT {
+        Err(PromptParseError::MessageInvalid(
+            MESSAGE_SEGMENTS_REASON_MESSAGE.to_string(),
+        ))
+    } else {
+        Ok(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Create words with configs that have them, the first in the way that
+    // it's configured.
+    fn configs() -> Vec<(&'static str, Config)> {
+        let memo = Config {
+            create_word: "memo".to_string(),
+            ..Config::default()
+        };
+        let aliased = Config {
+            create_aliases: vec!["new".into(), "save".into(), "code".into()],
+            ..Config::default()
+        };
+        vec![
+            ("partyskunk", Config::default()),
+            ("Memo", memo),
+            ("partyskunk", aliased.clone()),
+            ("SAVE", aliased),
+        ]
+    }
+
+    // Parses under each of `configs`, with "partyskunk" in the prompt
+    // standing for the create word, which has to make no difference.
+    fn parse(prompt: String) -> PromptParseResult {
+        let mut results = configs().into_iter().map(|(word, config)| {
+            super::parse(prompt.replace("partyskunk", word), &config)
+        });
+        let first = results.next().unwrap();
+        for result in results {
+            assert_eq!(result, first, "{:?}", prompt);
+        }
+        first
+    }
+
+    #[test]
+    fn it_parses_single_word_codes() {
+        for prompt in ["apple", " Apple\n", "APPLE"] {
+            let action = parse(prompt.to_string());
+            assert_eq!(action, Ok(Action::Read("apple".to_string(), None)));
+        }
+    }
+
+    #[test]
+    fn it_parses_multi_word_codes_in_any_spacing() {
+        for prompt in [
+            "apple boat",
+            "Apple  Boat",
+            "apple-boat",
+            "apple - boat",
+            "apple\nboat",
+            "APPLE--BOAT ",
+        ] {
+            let action = parse(prompt.to_string());
+            let code = "apple boat".to_string();
+            assert_eq!(action, Ok(Action::Read(code, None)));
+        }
+
+        let action = parse("apple boat cake".to_string());
+        let code = "apple boat cake".to_string();
+        assert_eq!(action, Ok(Action::Read(code, None)));
+    }
+
+    #[test]
+    fn it_parses_chosen_codes() {
+        let action = parse("partyskunk as Cake:  see you at 8".to_string());
+        let expected = Action::CreateAs("cake".into(), "see you at 8".into());
+        assert_eq!(action, Ok(expected));
+
+        let action = parse("partyskunk as cake:".to_string());
+        assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+    }
+
+    #[test]
+    fn it_creates_messages_that_start_like_options() {
+        for message in [
+            "ask me later",
+            "as",
+            "as cake is ready come down",
+            "as soon as you land, call me",
+            "as cake! hi",
+            "pin 42 hi",
+            "pin apple hi",
+            "for",
+            "for dinner bring wine",
+            "for 4105551234 hi",
+            "for +1410 hi",
+            "receipt",
+            "receipt attached",
+            "receipts please",
+            "receipt:attached",
+            "after",
+            "after lunch let's talk",
+            "after 30m2h hi",
+            "after 2 hi",
+            "after 99999999999999999999h hi",
+        ] {
+            let action = parse(format!("partyskunk {}", message));
+            assert_eq!(action, Ok(Action::Create(message.to_string())));
+        }
+    }
+
+    #[test]
+    fn it_parses_pins() {
+        let action = parse("partyskunk pin 4821 meet at the gate".to_string());
+        let expected =
+            Action::CreateWithPin("4821".into(), "meet at the gate".into());
+        assert_eq!(action, Ok(expected));
+
+        for prompt in ["apple 4821", "Apple-Boat 4821"] {
+            let action = parse(prompt.to_string());
+            let code = prompt.split(' ').next().unwrap();
+            let code = code.to_lowercase().replace('-', " ");
+            let expected = Action::Read(code, Some("4821".to_string()));
+            assert_eq!(action, Ok(expected));
+        }
+
+        let action = parse("partyskunk pin 4821".to_string());
+        assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+        for prompt in ["apple 4821 boat", "4821", "apple 123456789"] {
+            let action = parse(prompt.to_string());
+            assert_eq!(action, Err(PromptParseError::MalformedAction));
+        }
+    }
+
+    #[test]
+    fn it_parses_recipients() {
+        let action = parse("partyskunk for +14105551234 hi there".to_string());
+        let recipients = vec!["+14105551234".to_string()];
+        let expected = Action::CreateFor(recipients, "hi there".into());
+        assert_eq!(action, Ok(expected));
+
+        let prompt = "partyskunk for +14105551234, +442071234567 hi";
+        let action = parse(prompt.to_string());
+        let recipients = vec!["+14105551234".into(), "+442071234567".into()];
+        let expected = Action::CreateFor(recipients, "hi".into());
+        assert_eq!(action, Ok(expected));
+
+        let action = parse("partyskunk for +14105551234".to_string());
+        assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+    }
+
+    #[test]
+    fn it_parses_receipt_requests() {
+        let action = parse("partyskunk Receipt: see you there".to_string());
+        let expected = Action::CreateWithReceipt("see you there".to_string());
+        assert_eq!(action, Ok(expected));
+
+        let action = parse("partyskunk receipt:".to_string());
+        assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+    }
+
+    #[test]
+    fn it_parses_reveals() {
+        for (delay, secs) in [("2h", 7_200), ("45M", 2_700), ("1h30m", 5_400)] {
+            let prompt = format!("partyskunk after {} x marks the spot", delay);
+            let action = parse(prompt);
+            let delay = Duration::from_secs(secs);
+            let expected =
+                Action::CreateWithReveal(delay, "x marks the spot".into());
+            assert_eq!(action, Ok(expected));
+        }
+
+        let action = parse("partyskunk after 2h".to_string());
+        assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+    }
+
+    #[test]
+    fn it_parses_commands() {
+        for (prompt, expected) in [
+            ("partyskunk list", Action::List),
+            ("partyskunk Status", Action::Status),
+            ("partyskunk help", Action::Help),
+        ] {
+            assert_eq!(parse(prompt.to_string()), Ok(expected));
+        }
+
+        let action = parse("partyskunk status please".to_string());
+        let expected = Action::Create("status please".to_string());
+        assert_eq!(action, Ok(expected));
+    }
+
+    #[test]
+    fn it_parses_admin_actions() {
+        for (prompt, expected) in [
+            ("purge Apple-Boat", AdminAction::Purge("apple boat".into())),
+            ("ban +14105551234", AdminAction::Ban("+14105551234".into())),
+            (
+                "UNBAN +14105551234",
+                AdminAction::Unban("+14105551234".into()),
+            ),
+            ("stats", AdminAction::Stats),
+            ("maintenance ON", AdminAction::Maintenance(true)),
+            ("maintenance off", AdminAction::Maintenance(false)),
+        ] {
+            let action = parse(format!("partyskunk admin {}", prompt));
+            assert_eq!(action, Ok(Action::Admin(expected)));
+        }
+
+        for prompt in [
+            "admin",
+            "admin purge",
+            "admin purge apple 4821",
+            "admin ban 4105551234",
+            "admin stats now",
+            "admin maintenance maybe",
+            "admin reboot",
+        ] {
+            let action = parse(format!("partyskunk {}", prompt));
+            assert_eq!(action, Err(PromptParseError::MalformedAction));
+        }
+    }
+
+    #[test]
+    fn it_parses_replies() {
+        let action = parse("Apple Boat REPLY Thanks, See you!".to_string());
+        let code = "apple boat".to_string();
+        let expected = Action::Reply(code, None, "Thanks, See you!".into());
+        assert_eq!(action, Ok(expected));
+
+        let action = parse("apple 4821 reply on my way".to_string());
+        let pin = Some("4821".to_string());
+        let expected = Action::Reply("apple".into(), pin, "on my way".into());
+        assert_eq!(action, Ok(expected));
+
+        let action = parse("partyskunk Inbox".to_string());
+        assert_eq!(action, Ok(Action::Inbox));
+
+        let action = parse("apple reply".to_string());
+        assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+        let action = parse("apple! reply hi".to_string());
+        assert_eq!(action, Err(PromptParseError::MalformedAction));
+    }
+
+    #[test]
+    fn it_parses_only_configured_create_words() {
+        let aliased = configs().pop().unwrap().1;
+        let action = super::parse("Code hi there".to_string(), &aliased);
+        assert_eq!(action, Ok(Action::Create("hi there".to_string())));
+
+        let config = Config::default();
+        let action = super::parse("code hi there".to_string(), &config);
+        let expected = Action::Read("code hi there".to_string(), None);
+        assert_eq!(action, Ok(expected));
+
+        let memo = &configs()[1].1;
+        let action = super::parse("partyskunk list".to_string(), memo);
+        let expected = Action::Read("partyskunk list".to_string(), None);
+        assert_eq!(action, Ok(expected));
+    }
+
+    #[test]
+    fn it_limits_messages_by_characters_and_segments() {
+        for message in ["a".repeat(140), "🎉".repeat(50), "晚".repeat(140)] {
+            let action = parse(format!("partyskunk {}", message));
+            assert_eq!(action, Ok(Action::Create(message)));
+        }
+
+        for message in ["a".repeat(141), "🎉".repeat(101), "晚".repeat(141)]
+        {
+            let action = parse(format!("partyskunk {}", message));
+            assert!(matches!(action, Err(PromptParseError::MessageInvalid(_))));
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_codes() {
+        for prompt in ["apple!", "-apple", "apple-", "apple 42", ""] {
+            let action = parse(prompt.to_string());
+            assert_eq!(action, Err(PromptParseError::MalformedAction));
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// Words that mean something to the parser. Included by `parser.rs` and by
+// `build.rs`, so that there's only the one list.
+
+// The create word unless configured otherwise. Codebooks can't use it, so
+// that any of them works with the default config.
+pub const DEFAULT_CREATE_PROMPT_WORD: &str = "partyskunk";
+// Follows the create word, so it can't be created as a message by itself.
+const LIST_PROMPT_WORD: &str = "list";
+// Follows the create word, then the code to create and the message.
+const CREATE_AS_PROMPT_WORD: &str = "as";
+// Follows the create word, then the PIN and the message.
+const CREATE_WITH_PIN_PROMPT_WORD: &str = "pin";
+// Follows the create word, then the recipients and the message.
+const CREATE_FOR_PROMPT_WORD: &str = "for";
+// Follows the create word with a colon, then the message.
+const CREATE_WITH_RECEIPT_PROMPT_WORD: &str = "receipt";
+// Follows the create word, then the delay and the message.
+const CREATE_WITH_REVEAL_PROMPT_WORD: &str = "after";
+// Follows the create word, so it can't be created as a message by itself.
+const INBOX_PROMPT_WORD: &str = "inbox";
+// Follows the create word, so it can't be created as a message by itself.
+const STATUS_PROMPT_WORD: &str = "status";
+// Follows the create word, so it can't be created as a message by itself.
+const HELP_PROMPT_WORD: &str = "help";
+// Follows the create word, then one of the admin actions.
+const ADMIN_PROMPT_WORD: &str = "admin";
+// Follows a code and any PIN, then the reply.
+const REPLY_PROMPT_WORD: &str = "reply";
+// Words that mean something to `parse` and so can't be used in codes.
+pub const RESERVED_WORDS: &[&str] = &[
+    DEFAULT_CREATE_PROMPT_WORD,
+    LIST_PROMPT_WORD,
+    CREATE_AS_PROMPT_WORD,
+    CREATE_WITH_PIN_PROMPT_WORD,
+    CREATE_FOR_PROMPT_WORD,
+    CREATE_WITH_RECEIPT_PROMPT_WORD,
+    CREATE_WITH_REVEAL_PROMPT_WORD,
+    INBOX_PROMPT_WORD,
+    STATUS_PROMPT_WORD,
+    HELP_PROMPT_WORD,
+    ADMIN_PROMPT_WORD,
+    REPLY_PROMPT_WORD,
+];
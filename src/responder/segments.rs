@@ -0,0 +1,121 @@
+// Texts are sent in GSM-7 when every character has a place in it, and in
+// UCS-2 otherwise, which fits less than half as much into each segment. Each
+// segment is billed, so the count matters more than the length.
+const GSM_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+// These each take an escape along with themselves.
+const GSM_EXTENDED: &str = "\x0c^{}\\[~]|€";
+
+#[derive(Debug, PartialEq)]
+pub enum Encoding {
+    Gsm7,
+    Ucs2,
+}
+
+pub fn encoding(text: &str) -> Encoding {
+    let gsm = |c| GSM_BASIC.contains(c) || GSM_EXTENDED.contains(c);
+    if text.chars().all(gsm) {
+        Encoding::Gsm7
+    } else {
+        Encoding::Ucs2
+    }
+}
+
+// How many segments a text is sent as. Texts that don't fit in one are split,
+// with each part giving up some room to say how they join back up.
+pub fn count(text: &str) -> usize {
+    let (units, single, part) = match encoding(text) {
+        Encoding::Gsm7 => {
+            let septets = text
+                .chars()
+                .map(|c| if GSM_EXTENDED.contains(c) { 2 } else { 1 })
+                .sum();
+            (septets, 160, 153)
+        }
+        // Anything outside the basic multilingual plane, such as most emoji,
+        // takes two units.
+        Encoding::Ucs2 => (text.encode_utf16().count(), 70, 67),
+    };
+
+    if units <= single {
+        1
+    } else {
+        units.div_ceil(part)
+    }
+}
+
+// Cuts the text short, at the end of a word where there is one, so that it
+// takes up no more than `segments`.
+pub fn fit(text: &str, segments: usize) -> String {
+    const ELLIPSIS: &str = "...";
+    if count(text) <= segments {
+        return text.to_string();
+    }
+
+    let end = text
+        .char_indices()
+        .map(|(index, _)| index)
+        .rev()
+        .find(|&end| {
+            count(&format!("{}{}", &text[..end], ELLIPSIS)) <= segments
+        })
+        .unwrap_or(0);
+    let kept = &text[..end];
+    let kept = match kept.rfind(char::is_whitespace) {
+        Some(space) => &kept[..space],
+        None => kept,
+    };
+    format!("{}{}", kept.trim_end(), ELLIPSIS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_detects_encodings() {
+        assert_eq!(encoding("see you at 8 @ Café Ñ"), Encoding::Gsm7);
+        assert_eq!(encoding("tickets are [€20]"), Encoding::Gsm7);
+        assert_eq!(encoding("see you at 8 🎉"), Encoding::Ucs2);
+        assert_eq!(encoding("fête"), Encoding::Ucs2);
+        assert_eq!(encoding("晚上八点见"), Encoding::Ucs2);
+    }
+
+    #[test]
+    fn it_counts_segments() {
+        assert_eq!(count(""), 1);
+        assert_eq!(count(&"a".repeat(160)), 1);
+        assert_eq!(count(&"a".repeat(161)), 2);
+        assert_eq!(count(&"a".repeat(306)), 2);
+        assert_eq!(count(&"a".repeat(307)), 3);
+
+        // Extended characters take two septets.
+        assert_eq!(count(&"€".repeat(80)), 1);
+        assert_eq!(count(&"€".repeat(81)), 2);
+
+        // A single character outside of GSM-7 changes the whole text.
+        assert_eq!(count(&format!("{}é", "a".repeat(69))), 1);
+        assert_eq!(count(&format!("{}è", "a".repeat(69))), 1);
+        assert_eq!(count(&format!("{}ê", "a".repeat(70))), 2);
+
+        assert_eq!(count(&"🎉".repeat(35)), 1);
+        assert_eq!(count(&"🎉".repeat(36)), 2);
+        assert_eq!(count(&"晚".repeat(134)), 2);
+        assert_eq!(count(&"晚".repeat(135)), 3);
+    }
+
+    #[test]
+    fn it_fits_texts_into_segments() {
+        let text = "see you there ".repeat(30);
+        assert_eq!(fit("see you there", 1), "see you there");
+
+        // Cut at the end of a word.
+        let fitted = fit(&text, 2);
+        assert_eq!(count(&fitted), 2);
+        let kept = fitted.strip_suffix("...").unwrap();
+        assert!(text.starts_with(&format!("{} ", kept)));
+
+        // Cutting out whatever isn't GSM-7 can leave room for more.
+        let text = format!("{}🎉", "a ".repeat(100));
+        assert_eq!(fit(&text, 2), format!("{}a...", "a ".repeat(99)));
+    }
+}
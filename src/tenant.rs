@@ -0,0 +1,77 @@
+use crate::key_value_store::KeyValueStore;
+use serde_json as json;
+use std::collections::HashMap;
+use wasmbus_rpc::actor::prelude::*;
+
+// Which tenant each of the deployment's numbers belongs to, kept in the
+// kv-store as a JSON object of numbers to tenant names. Numbers left out
+// belong to the default tenant, which is everything from before there were
+// tenants.
+const TENANTS_KEY: &str = "partyskunk:tenants";
+
+// Each tenant's keys, config included, are kept apart under their own prefix.
+// Code words never have colons in them, so the prefix can't be mistaken for
+// one of the default tenant's keys.
+#[derive(Debug, PartialEq)]
+pub struct Tenant {
+    pub name: Option<String>,
+}
+
+impl Tenant {
+    // Tells whose number a message was sent to.
+    pub async fn resolve<T: KeyValueStore>(
+        to: &str,
+        store: &mut T,
+    ) -> RpcResult<Tenant> {
+        let tenants: HashMap<String, String> =
+            match store.get(TENANTS_KEY).await? {
+                Some(tenants) => json::from_str(&tenants)
+                    .map_err(|error| RpcError::Deser(error.to_string()))?,
+                None => HashMap::new(),
+            };
+
+        let name = tenants.get(to).cloned();
+        if let Some(name) = &name {
+            if name.is_empty() || name.contains(':') {
+                return Err(RpcError::Deser(format!(
+                    "{:?} can't be a tenant name",
+                    name
+                )));
+            }
+        }
+
+        Ok(Tenant { name })
+    }
+
+    pub fn prefix(&self) -> String {
+        match &self.name {
+            Some(name) => format!("tenants:{}:", name),
+            None => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_value_store::InMemory;
+
+    #[tokio::test]
+    async fn it_resolves_tenants_by_number() {
+        let mut store = InMemory::new();
+        let tenant = Tenant::resolve("+14105550000", &mut store).await;
+        assert_eq!(tenant.unwrap().prefix(), "");
+
+        let tenants = r#"{"+14105550000": "blue", "+14105550001": "a:b"}"#;
+        store
+            .set(TENANTS_KEY, tenants, Default::default())
+            .await
+            .unwrap();
+        let tenant = Tenant::resolve("+14105550000", &mut store).await;
+        assert_eq!(tenant.unwrap().prefix(), "tenants:blue:");
+        let tenant = Tenant::resolve("+14105559999", &mut store).await;
+        assert_eq!(tenant.unwrap().prefix(), "");
+        let tenant = Tenant::resolve("+14105550001", &mut store).await;
+        assert!(tenant.is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::http_client::{HttpClient, HttpRequest};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_urlencoded as urlencoded;
+use sha1::Sha1;
+use std::collections::HashMap;
+use wasmbus_rpc::actor::prelude::*;
+use wasmcloud_interface_httpserver::HttpRequest as WebhookRequest;
+
+// What twilio posts for each message that comes in.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Payload {
+    pub body: String,
+    pub from: String,
+    // One of the deployment's numbers, which tells whose message it is.
+    pub to: String,
+}
+
+// What twilio signs each request with, which shows that it came from them.
+#[derive(Debug, Default)]
+pub struct Signature {
+    // Where twilio posted to, as it was set up there.
+    url: String,
+    params: Vec<(String, String)>,
+    // From the X-Twilio-Signature header, if there was one.
+    signature: Option<String>,
+}
+
+impl Signature {
+    pub fn new(request: &WebhookRequest) -> Signature {
+        let header = |name: &str| {
+            let values = request.header.get(name)?;
+            values.first().map(String::as_str)
+        };
+
+        // Behind a proxy, twilio posted to wherever the request was forwarded
+        // from.
+        let scheme = header("x-forwarded-proto").unwrap_or("https");
+        let host = header("x-forwarded-host").or_else(|| header("host"));
+        let mut url = format!(
+            "{}://{}{}",
+            scheme,
+            host.unwrap_or_default(),
+            request.path
+        );
+        if !request.query_string.is_empty() {
+            url = format!("{}?{}", url, request.query_string);
+        }
+
+        Signature {
+            url,
+            params: urlencoded::from_bytes(&request.body).unwrap_or_default(),
+            signature: header("x-twilio-signature").map(String::from),
+        }
+    }
+
+    // Whether the request was signed with the account's auth token, the way
+    // that twilio describes: the URL and each parameter in order, HMAC-SHA1'd.
+    pub fn is_valid(&self, account: &Account) -> bool {
+        let signature = self.signature.as_deref().unwrap_or_default();
+        match BASE64.decode(signature) {
+            Ok(signature) => self.mac(account).verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn mac(&self, account: &Account) -> Hmac<Sha1> {
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(account.auth_token.as_bytes())
+                .expect("HMAC takes keys of any length");
+        mac.update(self.url.as_bytes());
+        let mut params: Vec<_> = self.params.iter().collect();
+        params.sort_by_key(|(key, _)| key);
+        for (key, value) in params {
+            mac.update(key.as_bytes());
+            mac.update(value.as_bytes());
+        }
+
+        mac
+    }
+
+    // Signed the way twilio would have.
+    #[cfg(test)]
+    pub fn signed(
+        url: &str,
+        params: &[(&str, &str)],
+        account: &Account,
+    ) -> Signature {
+        let mut signature = Signature {
+            url: url.to_string(),
+            params: params
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            signature: None,
+        };
+        let mac = signature.mac(account).finalize().into_bytes();
+        signature.signature = Some(BASE64.encode(mac));
+        signature
+    }
+}
+
+// What's needed to send messages out, which is left out of a deployment's
+// secrets to not send any.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Account {
+    // Points elsewhere for tests.
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    pub account_sid: String,
+    pub auth_token: String,
+    // The number to send from.
+    pub from: String,
+}
+
+fn default_api_url() -> String {
+    "https://api.twilio.com".to_string()
+}
+
+pub async fn send<C: HttpClient>(
+    to: &str,
+    body: &str,
+    account: &Account,
+    client: &mut C,
+) -> RpcResult<()> {
+    let url = format!(
+        "{}/2010-04-01/Accounts/{}/Messages.json",
+        account.api_url, account.account_sid
+    );
+    let form = [("To", to), ("From", &account.from), ("Body", body)];
+    let form = urlencoded::to_string(form)
+        .map_err(|error| RpcError::Ser(error.to_string()))?;
+    let credentials = format!("{}:{}", account.account_sid, account.auth_token);
+
+    let headers = HashMap::from([
+        (
+            "authorization".to_string(),
+            vec![format!("Basic {}", BASE64.encode(credentials))],
+        ),
+        (
+            "content-type".to_string(),
+            vec!["application/x-www-form-urlencoded".to_string()],
+        ),
+    ]);
+    let request = HttpRequest {
+        method: "POST".to_string(),
+        url,
+        headers,
+        body: form.into_bytes(),
+    };
+
+    let response = client.request(&request).await?;
+    if (200..300).contains(&response.status_code) {
+        Ok(())
+    } else {
+        Err(RpcError::Other(format!(
+            "twilio responded {}: {}",
+            response.status_code,
+            String::from_utf8_lossy(&response.body)
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json as json;
+    use std::fs;
+
+    fn account(auth_token: &str) -> Account {
+        Account {
+            api_url: default_api_url(),
+            account_sid: "AC123".to_string(),
+            auth_token: auth_token.to_string(),
+            from: "+18005551212".to_string(),
+        }
+    }
+
+    #[test]
+    fn it_checks_signatures() {
+        let url = "https://example.com/sms?tenant=blue";
+        let params = [
+            ("To", "+18005551212"),
+            ("From", "+14105551234"),
+            ("Body", "partyskunk admin stats"),
+        ];
+        let signature = Signature::signed(url, &params, &account("12345"));
+        let expected = "iqJ7BqGf+X3ivlkoTOaNCz6MNrU=";
+        assert_eq!(signature.signature.as_deref(), Some(expected));
+        assert!(signature.is_valid(&account("12345")));
+        assert!(!signature.is_valid(&account("54321")));
+
+        let mut tampered = Signature::signed(url, &params, &account("12345"));
+        tampered.params[1].1 = "+14105550000".to_string();
+        assert!(!tampered.is_valid(&account("12345")));
+        assert!(!Signature::default().is_valid(&account("12345")));
+    }
+
+    #[test]
+    fn it_reads_signatures_from_requests() {
+        let request = fs::read_to_string("test/fixtures/request_1.json");
+        let request: WebhookRequest =
+            json::from_str(&request.unwrap()).unwrap();
+        let signature = Signature::new(&request);
+        assert_eq!(signature.url, "http://h7umvb.tunnel.pyjam.as/");
+        let body = ("Body".to_string(), "test".to_string());
+        assert!(signature.params.contains(&body));
+        let expected = "LZ1v18jNpFzLDzvqg4LbUExKlgI=";
+        assert_eq!(signature.signature.as_deref(), Some(expected));
+    }
+}
@@ -3,8 +3,7 @@
 # Keypad layout image:
 # https://en.wikipedia.org/wiki/Telephone_keypad#/media/File:Telephone-keypad2.svg
 
-CODES_FILE = File.join(File.dirname(__FILE__), "../src/responder/exchange/codes.rs")
-CODES_FILE_DELIMITER = "// SEARCH_SCRIPT_BOUNDARY"
+CODES_FILE = File.join(File.dirname(__FILE__), "../../../../codebooks/words/basic.txt")
 CANDIDATES_FILE = File.join(File.dirname(__FILE__), "./candidates")
 RESULTS_FILE = File.join(File.dirname(__FILE__), "./results")
 PHONE_NUMBER_SIZE = 10
@@ -49,9 +48,9 @@ def get_candidates
       puts CANDIDATES_FILE, "not found"
       puts "checking start words and building candidates"
 
-      file = File.read(CODES_FILE)
-      data = file.split(CODES_FILE_DELIMITER)[1]
-      words = eval(data)
+      words = File.readlines(CODES_FILE, chomp: true).reject do |line|
+        line.empty? || line.start_with?("#")
+      end
 
       size_words = {}
       size_start_words = {}